@@ -0,0 +1,31 @@
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+use criterion::Throughput;
+use yolol_yaml_deserializer::YamlDocument;
+
+/// A network file shaped like the ones shipped with the game: many devices,
+/// each a map of short scalar fields.
+fn device_file(devices: usize) -> String {
+    let mut s = String::from("devices:\n");
+    for i in 0..devices {
+        s.push_str(&format!(
+            "- name: button_{i}\n  type: !button\n  fields:\n    ButtonState: 0\n    ButtonOnStateValue: 1\n    ButtonOffStateValue: 0\n    ButtonStyle: toggle\n    Label: \"Button {i}\"\n  tags: [a, b, c]\n",
+            i = i
+        ));
+    }
+    s
+}
+
+fn parse(c: &mut Criterion) {
+    let src = device_file(20_000);
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(src.len() as u64));
+    group.bench_function("scalar_heavy", |b| {
+        b.iter(|| src.parse::<YamlDocument>().unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, parse);
+criterion_main!(benches);
@@ -0,0 +1,81 @@
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::ext::IdentExt;
+use syn::parse_macro_input;
+use syn::Data;
+use syn::DataStruct;
+use syn::DeriveInput;
+use syn::Error;
+use syn::Field;
+use syn::Fields;
+use syn::LitStr;
+
+#[proc_macro_derive(FromYaml, attributes(yaml))]
+pub fn derive_from_yaml(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = match &input.data {
+        Data::Struct(DataStruct {
+            fields: Fields::Named(fields),
+            ..
+        }) => &fields.named,
+        _ => {
+            return Err(Error::new_spanned(
+                input,
+                "FromYaml can only be derived for structs with named fields",
+            ))
+        }
+    };
+    let inits = fields
+        .iter()
+        .map(|field| {
+            let ident = &field.ident;
+            let key = key(field)?;
+            Ok(quote! {
+                #ident: ::yolol_yaml_deserializer::FromYaml::from_yaml(&el[#key]).map_err(|e| {
+                    ::yolol_yaml_deserializer::YamlError::AtPath(#key.into(), ::std::boxed::Box::new(e))
+                })?
+            })
+        })
+        .collect::<syn::Result<Vec<_>>>()?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::yolol_yaml_deserializer::FromYaml for #name #ty_generics #where_clause {
+            fn from_yaml(
+                el: &::yolol_yaml_deserializer::YamlElement,
+            ) -> ::std::result::Result<Self, ::yolol_yaml_deserializer::YamlError> {
+                if el.as_map().is_none() {
+                    return Err(el.mismatch("map"));
+                }
+                Ok(Self { #(#inits,)* })
+            }
+        }
+    })
+}
+
+fn key(field: &Field) -> syn::Result<String> {
+    let mut key = None;
+    for attr in field
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("yaml"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename") {
+                key = Some(meta.value()?.parse::<LitStr>()?.value());
+                Ok(())
+            } else {
+                Err(meta.error("unsupported yaml attribute"))
+            }
+        })?;
+    }
+    Ok(key.unwrap_or_else(|| field.ident.as_ref().unwrap().unraw().to_string()))
+}
@@ -0,0 +1,196 @@
+use std::fs::File;
+use std::io;
+use std::sync::Arc;
+
+use libyaml::Encoding;
+use libyaml::Event;
+use libyaml::ScalarStyle;
+
+use crate::Events;
+use crate::ScalarResolver;
+use crate::YamlDocument;
+use crate::YamlError;
+use crate::MAX_DEPTH;
+use crate::MAX_NODES;
+
+/// What to do when a map repeats a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// The last value is kept, silently.
+    LastWins,
+    /// The repeat is a `DuplicateKey` error.
+    Reject,
+    /// The last value is kept and the repeat is listed by
+    /// `YamlDocument::duplicate_keys`.
+    Collect,
+}
+
+/// Loading options for `YamlDocument`. The defaults match
+/// `YamlDocument::new`: merge keys are applied, map entries keep their
+/// source order, aliases are kept as aliases, the encoding is detected and
+/// nesting is limited to 128 levels.
+#[derive(Debug, Clone)]
+pub struct YamlDocumentBuilder {
+    resolve_aliases: bool,
+    preserve_order: bool,
+    merge_keys: bool,
+    pub(crate) duplicate_keys: DuplicateKeys,
+    pub(crate) max_depth: usize,
+    pub(crate) max_nodes: usize,
+    pub(crate) resolver: Option<Arc<dyn ScalarResolver>>,
+    encoding: Option<Encoding>,
+}
+
+impl Default for YamlDocumentBuilder {
+    fn default() -> Self {
+        Self {
+            resolve_aliases: false,
+            preserve_order: true,
+            merge_keys: true,
+            duplicate_keys: DuplicateKeys::LastWins,
+            max_depth: MAX_DEPTH,
+            max_nodes: MAX_NODES,
+            resolver: None,
+            encoding: None,
+        }
+    }
+}
+
+impl YamlDocumentBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces every alias with a copy of its anchor once the document is
+    /// loaded, as `YamlDocument::resolve_all` does.
+    pub fn resolve_aliases(mut self, enable: bool) -> Self {
+        self.resolve_aliases = enable;
+        self
+    }
+
+    /// When disabled, map entries are sorted by key.
+    pub fn preserve_order(mut self, enable: bool) -> Self {
+        self.preserve_order = enable;
+        self
+    }
+
+    /// When disabled, `<<` is kept as an ordinary key.
+    pub fn merge_keys(mut self, enable: bool) -> Self {
+        self.merge_keys = enable;
+        self
+    }
+
+    pub fn duplicate_keys(mut self, policy: DuplicateKeys) -> Self {
+        self.duplicate_keys = policy;
+        self
+    }
+
+    /// How deeply sequences and maps may nest before loading fails with
+    /// `DepthExceeded`, in the source and in the tree that expanding
+    /// aliases or merge keys builds from it. The tree is built and dropped
+    /// recursively, so this guards the stack against hostile input.
+    /// Defaults to 128.
+    pub fn max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// How many nodes expanding aliases and merge keys may copy before
+    /// failing with `ExpansionLimit`, which stops alias bombs from
+    /// exhausting memory. Nodes written out in the source don't count.
+    /// Applies to loading and to later `resolve` calls on the document.
+    /// Defaults to a million.
+    pub fn max_nodes(mut self, nodes: usize) -> Self {
+        self.max_nodes = nodes;
+        self
+    }
+
+    /// Types untagged plain scalars as they are read, for instance with
+    /// `CoreSchema`. The result is kept apart from source tags, in
+    /// `resolved_tag`, and is not written out.
+    pub fn scalar_resolver(mut self, resolver: impl ScalarResolver + 'static) -> Self {
+        self.resolver = Some(Arc::new(resolver));
+        self
+    }
+
+    /// Overrides detection, which only recognizes UTF-16 by its byte order
+    /// mark and reads anything else as UTF-8.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = Some(encoding);
+        self
+    }
+
+    pub fn build_from_str(&self, s: &str) -> Result<YamlDocument, YamlError> {
+        self.build_from_reader(s.as_bytes())
+    }
+
+    pub fn build_from_slice(&self, bytes: &[u8]) -> Result<YamlDocument, YamlError> {
+        self.build_from_reader(io::Cursor::new(bytes))
+    }
+
+    pub fn build_from_file(&self, path: &str) -> Result<YamlDocument, YamlError> {
+        self.build_from_reader(File::open(path)?)
+    }
+
+    /// Parses a single document. Anchors are scoped to the document that
+    /// defines them, so a stream with several documents is a
+    /// `MultipleDocuments` error; use `build_from_reader_multi` for those.
+    pub fn build_from_reader(&self, reader: impl io::Read) -> Result<YamlDocument, YamlError> {
+        let mut documents = self.build_from_reader_multi(reader)?.into_iter();
+        let s = documents.next().unwrap_or_else(YamlDocument::empty);
+        if documents.next().is_some() {
+            return Err(YamlError::MultipleDocuments);
+        }
+        Ok(s)
+    }
+
+    pub fn build_from_reader_multi(
+        &self,
+        reader: impl io::Read,
+    ) -> Result<Vec<YamlDocument>, YamlError> {
+        let iter = &mut Events::new(reader, self.encoding)?;
+        let mut documents = vec![];
+        loop {
+            match iter.next()? {
+                Event::StreamStart { .. } => {}
+                Event::DocumentStart {
+                    version,
+                    tags,
+                    implicit,
+                } => {
+                    let document = YamlDocument::document(iter, self, version, tags, implicit)?;
+                    documents.push(self.finish(document)?)
+                }
+                Event::StreamEnd => return Ok(documents),
+                event => return Err(iter.rejected(event)),
+            }
+        }
+    }
+
+    /// The tag the scalar resolver gives a scalar, if it is plain and
+    /// untagged.
+    pub(crate) fn resolve(
+        &self,
+        value: &str,
+        tag: Option<&str>,
+        style: Option<ScalarStyle>,
+    ) -> Option<String> {
+        match (&self.resolver, style) {
+            (Some(resolver), Some(ScalarStyle::Plain)) if tag.is_none() => resolver.resolve(value),
+            _ => None,
+        }
+    }
+
+    fn finish(&self, mut document: YamlDocument) -> Result<YamlDocument, YamlError> {
+        if self.merge_keys {
+            document.merge_keys()?;
+        }
+        if self.resolve_aliases {
+            document.resolve_all()?;
+        }
+        if !self.preserve_order {
+            document.sort_keys();
+        }
+        Ok(document)
+    }
+}
@@ -0,0 +1,363 @@
+use std::fmt;
+use std::slice;
+
+use indexmap::map;
+use serde::de;
+use serde::de::DeserializeSeed;
+use serde::de::IntoDeserializer;
+use serde::de::Visitor;
+use serde::Deserialize;
+use serde::Deserializer;
+
+use crate::BoxedYamlElement;
+use crate::Typed;
+use crate::YamlElement;
+use crate::YamlError;
+
+/// Deserializes any `serde` type from a parsed element, typing scalars for
+/// self-describing targets as `to_json` does. An alias can't be
+/// deserialized, since it isn't followed; call `resolve_all` beforehand.
+/// Tags and anchors are ignored.
+pub fn from_element<'de, T: Deserialize<'de>>(el: &'de YamlElement) -> Result<T, YamlError> {
+    T::deserialize(el)
+}
+
+impl de::Error for YamlError {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        YamlError::Message(msg.to_string())
+    }
+}
+
+macro_rules! deserialize_number {
+    ($($method:ident => $visit:ident($accessor:ident, $expected:literal),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+                match self.$accessor() {
+                    Some(n) => visitor.$visit(n),
+                    None => Err(self.mismatch($expected)),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for &'de YamlElement {
+    type Error = YamlError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self.typed_scalar() {
+            Some(Typed::Null) => visitor.visit_unit(),
+            Some(Typed::Bool(b)) => visitor.visit_bool(b),
+            Some(Typed::Int(n)) => visitor.visit_i64(n),
+            Some(Typed::UInt(n)) => visitor.visit_u64(n),
+            Some(Typed::Float(n)) => visitor.visit_f64(n),
+            Some(Typed::Str(s)) => visitor.visit_borrowed_str(s),
+            None => match self {
+                YamlElement::Map(..) => self.deserialize_map(visitor),
+                YamlElement::Set(..) => self.deserialize_seq(visitor),
+                _ => Err(self.mismatch("resolved element")),
+            },
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self.as_bool() {
+            Some(b) => visitor.visit_bool(b),
+            None => Err(self.mismatch("boolean")),
+        }
+    }
+
+    deserialize_number! {
+        deserialize_i8 => visit_i64(as_i64, "integer"),
+        deserialize_i16 => visit_i64(as_i64, "integer"),
+        deserialize_i32 => visit_i64(as_i64, "integer"),
+        deserialize_i64 => visit_i64(as_i64, "integer"),
+        deserialize_u8 => visit_u64(as_u64, "unsigned integer"),
+        deserialize_u16 => visit_u64(as_u64, "unsigned integer"),
+        deserialize_u32 => visit_u64(as_u64, "unsigned integer"),
+        deserialize_u64 => visit_u64(as_u64, "unsigned integer"),
+        deserialize_f32 => visit_f64(as_f64, "float"),
+        deserialize_f64 => visit_f64(as_f64, "float"),
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self {
+            YamlElement::Scalar(s, ..) => visitor.visit_borrowed_str(s),
+            _ => Err(self.mismatch("string")),
+        }
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self {
+            YamlElement::Scalar(s, ..) => visitor.visit_borrowed_bytes(s.as_bytes()),
+            _ => Err(self.mismatch("bytes")),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        if self.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        if self.is_null() {
+            visitor.visit_unit()
+        } else {
+            Err(self.mismatch("null"))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self {
+            YamlElement::Set(set, ..) => visitor.visit_seq(SeqAccess { iter: set.iter() }),
+            _ => Err(self.mismatch("sequence")),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        match self {
+            YamlElement::Map(map, ..) => visitor.visit_map(MapAccess {
+                iter: map.iter(),
+                value: None,
+            }),
+            _ => Err(self.mismatch("map")),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        match self {
+            YamlElement::Scalar(s, ..) => visitor.visit_enum(s.as_str().into_deserializer()),
+            YamlElement::Map(map, ..) if map.len() == 1 => {
+                let (variant, value) = map.iter().next().unwrap();
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            _ => Err(self.mismatch("enum variant")),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqAccess<'de> {
+    iter: slice::Iter<'de, BoxedYamlElement>,
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess<'de> {
+    type Error = YamlError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, YamlError> {
+        self.iter
+            .next()
+            .map(|el| seed.deserialize(el.as_ref()))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct MapAccess<'de> {
+    iter: map::Iter<'de, String, BoxedYamlElement>,
+    value: Option<&'de YamlElement>,
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess<'de> {
+    type Error = YamlError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, YamlError> {
+        match self.iter.next() {
+            Some((key, value)) => {
+                self.value = Some(value);
+                seed.deserialize(Key(key)).map(Some)
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, YamlError> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value requested before key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+struct EnumAccess<'de> {
+    variant: &'de str,
+    value: &'de YamlElement,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess<'de> {
+    type Error = YamlError;
+    type Variant = &'de YamlElement;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), YamlError> {
+        Ok((seed.deserialize(Key(self.variant))?, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for &'de YamlElement {
+    type Error = YamlError;
+
+    fn unit_variant(self) -> Result<(), YamlError> {
+        Deserialize::deserialize(self)
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, YamlError> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        self.deserialize_map(visitor)
+    }
+}
+
+/// Map keys are stored as strings; numeric and boolean key types are parsed
+/// back out of them on request.
+struct Key<'de>(&'de str);
+
+macro_rules! deserialize_key {
+    ($($method:ident => $visit:ident($ty:ty),)*) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+                match self.0.parse::<$ty>() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(YamlError::TypeMismatch {
+                        expected: stringify!($ty),
+                        found: format!("key `{}`", self.0),
+                    }),
+                }
+            }
+        )*
+    };
+}
+
+impl<'de> Deserializer<'de> for Key<'de> {
+    type Error = YamlError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, YamlError> {
+        visitor.visit_borrowed_str(self.0)
+    }
+
+    deserialize_key! {
+        deserialize_bool => visit_bool(bool),
+        deserialize_i8 => visit_i8(i8),
+        deserialize_i16 => visit_i16(i16),
+        deserialize_i32 => visit_i32(i32),
+        deserialize_i64 => visit_i64(i64),
+        deserialize_u8 => visit_u8(u8),
+        deserialize_u16 => visit_u16(u16),
+        deserialize_u32 => visit_u32(u32),
+        deserialize_u64 => visit_u64(u64),
+        deserialize_f32 => visit_f32(f32),
+        deserialize_f64 => visit_f64(f64),
+        deserialize_char => visit_char(char),
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, YamlError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128 str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct enum identifier ignored_any
+    }
+}
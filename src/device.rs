@@ -0,0 +1,48 @@
+use crate::YamlElement;
+
+/// One field of a Yolol device, such as `LightOn: 1`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceField {
+    pub name: String,
+    pub value: YamlElement,
+    /// The `type` entry when the field spells one out, otherwise the tag of
+    /// its value.
+    pub tag: Option<String>,
+}
+
+impl YamlElement {
+    /// Reads a `fields:` block in either of the shapes found in device
+    /// files: a map from field name to value, or a sequence of maps with
+    /// `name`, `value` and an optional `type`. Returns `None` for anything
+    /// else, including an entry without a `name`.
+    pub fn as_device_fields(&self) -> Option<Vec<DeviceField>> {
+        match self {
+            YamlElement::Map(..) => Some(
+                self.iter_map()
+                    .map(|(name, value)| DeviceField {
+                        name: name.to_string(),
+                        value: value.clone(),
+                        tag: value.get_tag().map(str::to_string),
+                    })
+                    .collect(),
+            ),
+            YamlElement::Set(..) => self
+                .iter_seq()
+                .map(|field| {
+                    let name = field.get("name")?.as_str()?;
+                    let value = field.get("value").unwrap_or(&YamlElement::None);
+                    let tag = match field.get("type") {
+                        Some(kind) => Some(kind.as_str()?),
+                        None => value.get_tag(),
+                    };
+                    Some(DeviceField {
+                        name: name.to_string(),
+                        value: value.clone(),
+                        tag: tag.map(str::to_string),
+                    })
+                })
+                .collect(),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::PathSegment;
+use crate::YamlElement;
+
+/// One difference found by `YamlElement::diff`, at a path from the compared
+/// elements as `YamlElement::walk` spells it.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change<'a> {
+    Added {
+        path: Vec<PathSegment>,
+        new: &'a YamlElement,
+    },
+    Removed {
+        path: Vec<PathSegment>,
+        old: &'a YamlElement,
+    },
+    Modified {
+        path: Vec<PathSegment>,
+        old: &'a YamlElement,
+        new: &'a YamlElement,
+    },
+}
+
+impl YamlElement {
+    /// Lists what changed from `self` to `other`. Maps are compared key by
+    /// key, in `self`'s order followed by keys only `other` has, and
+    /// sequences index by index. Any other pair of nodes is modified when
+    /// their values or tags differ; styles and anchors are ignored.
+    pub fn diff<'a>(&'a self, other: &'a YamlElement) -> Vec<Change<'a>> {
+        let mut changes = vec![];
+        diff(self, other, &mut vec![], &mut changes);
+        changes
+    }
+}
+
+fn diff<'a>(
+    old: &'a YamlElement,
+    new: &'a YamlElement,
+    path: &mut Vec<PathSegment>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    match (old, new) {
+        (YamlElement::Map(a, ..), YamlElement::Map(b, ..)) => {
+            for (k, v) in a {
+                path.push(PathSegment::Key(k.clone()));
+                match b.get(k) {
+                    Some(w) => diff(v, w, path, changes),
+                    None => changes.push(Change::Removed {
+                        path: path.clone(),
+                        old: v,
+                    }),
+                }
+                path.pop();
+            }
+            for (k, w) in b.iter().filter(|(k, _)| !a.contains_key(*k)) {
+                path.push(PathSegment::Key(k.clone()));
+                changes.push(Change::Added {
+                    path: path.clone(),
+                    new: w,
+                });
+                path.pop();
+            }
+        }
+        (YamlElement::Set(a, ..), YamlElement::Set(b, ..)) => {
+            for i in 0..a.len().max(b.len()) {
+                path.push(PathSegment::Index(i));
+                match (a.get(i), b.get(i)) {
+                    (Some(v), Some(w)) => diff(v, w, path, changes),
+                    (Some(v), None) => changes.push(Change::Removed {
+                        path: path.clone(),
+                        old: v,
+                    }),
+                    (None, Some(w)) => changes.push(Change::Added {
+                        path: path.clone(),
+                        new: w,
+                    }),
+                    (None, None) => {}
+                }
+                path.pop();
+            }
+        }
+        _ if old.structural_eq(new) && old.get_tag() == new.get_tag() => {}
+        _ => changes.push(Change::Modified {
+            path: path.clone(),
+            old,
+            new,
+        }),
+    }
+}
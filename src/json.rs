@@ -0,0 +1,50 @@
+use serde_json::Map;
+use serde_json::Number;
+use serde_json::Value;
+
+use crate::Typed;
+use crate::YamlDocument;
+use crate::YamlElement;
+use crate::YamlError;
+
+impl YamlElement {
+    /// Converts to JSON, typing scalars as the typed accessors read them:
+    /// standard tags pick the type, and untagged quoted or block scalars
+    /// stay strings. Non-finite floats become `null`, as JSON has
+    /// no way to spell them. An alias is a `TypeMismatch`, since the element
+    /// alone can't reach its anchor; `YamlDocument::to_json` expands them.
+    pub fn to_json(&self) -> Result<Value, YamlError> {
+        Ok(match self.typed_scalar() {
+            Some(Typed::Null) => Value::Null,
+            Some(Typed::Bool(b)) => Value::Bool(b),
+            Some(Typed::Int(n)) => n.into(),
+            Some(Typed::UInt(n)) => n.into(),
+            Some(Typed::Float(n)) => Number::from_f64(n).map_or(Value::Null, Value::Number),
+            Some(Typed::Str(s)) => Value::String(s.to_string()),
+            None => match self {
+                YamlElement::Map(map, ..) => Value::Object(
+                    map.iter()
+                        .map(|(k, v)| Ok((k.clone(), v.to_json()?)))
+                        .collect::<Result<Map<_, _>, YamlError>>()?,
+                ),
+                YamlElement::Set(set, ..) => Value::Array(
+                    set.iter()
+                        .map(|v| v.to_json())
+                        .collect::<Result<_, YamlError>>()?,
+                ),
+                _ => return Err(self.mismatch("resolved element")),
+            },
+        })
+    }
+}
+
+impl YamlDocument {
+    /// Converts the root to JSON as `YamlElement::to_json` does, with every
+    /// alias expanded first. An empty document is `null`.
+    pub fn to_json(&self) -> Result<Value, YamlError> {
+        match self.root.first() {
+            Some(root) => self.resolve(root)?.to_json(),
+            None => Ok(Value::Null),
+        }
+    }
+}
@@ -1,23 +1,275 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::cmp::Ordering;
 use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::ffi::CStr;
+use std::fmt;
 use std::fs::File;
-use std::iter::Peekable;
+use std::io;
+use std::iter;
+use std::mem;
+use std::mem::MaybeUninit;
 use std::ops::Index;
+use std::ops::IndexMut;
+use std::os::raw::c_char;
+use std::rc::Rc;
+use std::slice;
 use std::str::FromStr;
+use std::sync::Arc;
 
-use libyaml::Event;
+use indexmap::IndexMap;
+use libyaml::Emitter;
+use libyaml::EmitterBuilder;
+use libyaml::EmitterError;
 use libyaml::Parser;
-use libyaml::ParserIter;
+use libyaml::ParserBuilder;
+use libyaml::ParserError;
+use libyaml_sys as sys;
 
-pub type YamlMap = BTreeMap<String, BoxedYamlElement>;
+pub use builder::DuplicateKeys;
+pub use builder::YamlDocumentBuilder;
+#[cfg(feature = "serde")]
+pub use de::from_element;
+pub use device::DeviceField;
+pub use diff::Change;
+pub use libyaml::Encoding;
+pub use libyaml::Event;
+pub use libyaml::MappingStyle;
+pub use libyaml::ScalarStyle;
+pub use libyaml::SequenceStyle;
+pub use libyaml::TagDirective;
+pub use libyaml::VersionDirective;
+pub use path::TypedPath;
+pub use resolver::CoreSchema;
+pub use resolver::ScalarResolver;
+pub use schema::Kind;
+pub use schema::Schema;
+pub use schema::ValidationError;
+#[cfg(feature = "derive")]
+pub use yolol_yaml_deserializer_derive::FromYaml;
+
+mod builder;
+#[cfg(feature = "serde")]
+mod de;
+mod device;
+mod diff;
+#[cfg(feature = "json")]
+mod json;
+mod macros;
+mod path;
+mod resolver;
+mod schema;
+#[cfg(feature = "serde")]
+mod ser;
+#[cfg(test)]
+mod tests;
+// Lets the tests use `#[derive(FromYaml)]`, whose output names this crate.
+#[cfg(all(test, feature = "derive"))]
+extern crate self as yolol_yaml_deserializer;
+#[cfg(feature = "chrono")]
+mod timestamp;
+
+/// Keys are the text of the key scalar; its tag and style are not kept.
+/// Repeating a key, even spelled as `!!int 1` after `1`, is handled by
+/// `DuplicateKeys`, while keys of different types with the same text, such
+/// as `1` and `"1"`, fail to load with `KeyCollision`. Typed keys are
+/// recovered by parsing the text, which is what the serde support does for
+/// integer and boolean keys.
+pub type YamlMap = IndexMap<String, BoxedYamlElement>;
 pub type YamlSet = Vec<BoxedYamlElement>;
 type BoxedYamlElement = Box<YamlElement>;
 
+#[derive(Debug)]
+pub enum YamlError {
+    Io(io::Error),
+    Parse(ParserError),
+    UnexpectedEvent(Event, Position),
+    Syntax {
+        problem: String,
+        position: Position,
+    },
+    InvalidEncoding {
+        problem: String,
+        offset: usize,
+    },
+    UnexpectedEnd,
+    UnknownAnchor(String),
+    CyclicAlias(String),
+    MultipleDocuments,
+    DuplicateKey(String, Position),
+    /// A key with the text of an earlier key of its map but another type,
+    /// such as `1` after `"1"`, which `YamlMap` can't keep apart.
+    KeyCollision(String, Position),
+    /// Nesting deeper than `YamlDocumentBuilder::max_depth`, at the node
+    /// that crossed it or, when expanding aliases or merge keys got there,
+    /// with no position.
+    DepthExceeded(Option<Position>),
+    /// Expanding aliases or merge keys would copy more nodes than the
+    /// limit.
+    ExpansionLimit(usize),
+    /// A sequence or map used as a map key, which `YamlMap` can't store.
+    ComplexKey(Position),
+    /// Nothing is at the slash-delimited path.
+    MissingPath(String),
+    /// The node at the path didn't convert.
+    AtPath(String, Box<YamlError>),
+    UnknownVariant {
+        found: String,
+        variants: Vec<String>,
+    },
+    TypeMismatch {
+        expected: &'static str,
+        found: String,
+    },
+    Message(String),
+}
+
+impl fmt::Display for YamlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            YamlError::Io(e) => write!(f, "io error: {}", e),
+            YamlError::Parse(e) => write!(f, "{}", e),
+            YamlError::UnexpectedEvent(e, position) => {
+                write!(f, "unexpected {} at {}", describe(e), position)
+            }
+            YamlError::Syntax { problem, position } => write!(f, "{} at {}", problem, position),
+            YamlError::InvalidEncoding { problem, offset } => {
+                write!(f, "{} at byte {}", problem, offset)
+            }
+            YamlError::UnexpectedEnd => write!(f, "unexpected end of stream"),
+            YamlError::UnknownAnchor(a) => write!(f, "unknown anchor: {}", a),
+            YamlError::CyclicAlias(a) => write!(f, "cyclic alias: {}", a),
+            YamlError::MultipleDocuments => {
+                write!(
+                    f,
+                    "stream contains more than one document; load it with new_multi"
+                )
+            }
+            YamlError::DuplicateKey(key, position) => {
+                write!(f, "duplicate key `{}` at {}", key, position)
+            }
+            YamlError::KeyCollision(key, position) => {
+                write!(
+                    f,
+                    "key `{}` at {} has another type than the earlier one",
+                    key, position
+                )
+            }
+            YamlError::DepthExceeded(Some(position)) => {
+                write!(f, "nesting too deep at {}", position)
+            }
+            YamlError::DepthExceeded(None) => f.write_str("nesting too deep once expanded"),
+            YamlError::ExpansionLimit(limit) => {
+                write!(f, "alias expansion exceeds {} nodes", limit)
+            }
+            YamlError::ComplexKey(position) => {
+                write!(f, "unsupported complex key at {}", position)
+            }
+            YamlError::MissingPath(path) => write!(f, "nothing at /{}", path),
+            YamlError::AtPath(path, e) => write!(f, "at /{}: {}", path, e),
+            YamlError::UnknownVariant { found, variants } => {
+                write!(
+                    f,
+                    "expected one of `{}`, found {}",
+                    variants.join("`, `"),
+                    found
+                )
+            }
+            YamlError::TypeMismatch { expected, found } => {
+                write!(f, "expected {}, found {}", expected, found)
+            }
+            YamlError::Message(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl Error for YamlError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            YamlError::Io(e) => Some(e),
+            YamlError::Parse(e) => Some(e),
+            YamlError::AtPath(_, e) => Some(e.as_ref()),
+            YamlError::UnexpectedEvent(..)
+            | YamlError::Syntax { .. }
+            | YamlError::InvalidEncoding { .. }
+            | YamlError::UnexpectedEnd
+            | YamlError::UnknownAnchor(_)
+            | YamlError::CyclicAlias(_)
+            | YamlError::MultipleDocuments
+            | YamlError::DuplicateKey(..)
+            | YamlError::KeyCollision(..)
+            | YamlError::DepthExceeded(_)
+            | YamlError::ComplexKey(_)
+            | YamlError::ExpansionLimit(_)
+            | YamlError::MissingPath(_)
+            | YamlError::UnknownVariant { .. }
+            | YamlError::TypeMismatch { .. }
+            | YamlError::Message(_) => None,
+        }
+    }
+}
+
+fn describe(event: &Event) -> &'static str {
+    match event {
+        Event::StreamStart { .. } => "stream start",
+        Event::StreamEnd => "stream end",
+        Event::DocumentStart { .. } => "document start",
+        Event::DocumentEnd { .. } => "document end",
+        Event::Alias { .. } => "alias",
+        Event::Scalar { .. } => "scalar",
+        Event::SequenceStart { .. } => "sequence",
+        Event::SequenceEnd => "sequence end",
+        Event::MappingStart { .. } => "mapping",
+        Event::MappingEnd => "mapping end",
+    }
+}
+
+impl From<io::Error> for YamlError {
+    fn from(e: io::Error) -> Self {
+        YamlError::Io(e)
+    }
+}
+
+impl From<ParserError> for YamlError {
+    fn from(e: ParserError) -> Self {
+        match e {
+            ParserError::IoError(e) => YamlError::Io(e),
+            e => YamlError::Parse(e),
+        }
+    }
+}
+
+/// The nodes of one parsed document. Comments are not kept: libyaml's parser
+/// drops them while scanning and has no option to report them, so they are
+/// lost on a load and emit round-trip.
+///
+/// Documents and elements own plain data and are `Send` and `Sync`, so a
+/// document parsed once can be shared between threads with `into_shared`.
 #[derive(Debug)]
 pub struct YamlDocument {
     root: YamlSet,
     anchor: YamlMap,
+    /// Anchors defined more than once, while parsing.
+    redefined: BTreeSet<String>,
+    duplicates: Vec<(String, Position)>,
+    version: Option<VersionDirective>,
+    tags: Vec<TagDirective>,
+    explicit_start: bool,
+    explicit_end: bool,
+    max_depth: usize,
+    max_nodes: usize,
 }
 
+// Keeps the `Send` and `Sync` promise above from being broken silently.
+const _: fn() = || {
+    fn shareable<T: Send + Sync>() {}
+    shareable::<YamlDocument>();
+    shareable::<YamlElement>();
+};
+
 impl std::ops::Deref for YamlDocument {
     type Target = YamlSet;
 
@@ -26,21 +278,69 @@ impl std::ops::Deref for YamlDocument {
     }
 }
 
-#[derive(Debug, Clone, Ord, Eq, PartialEq, PartialOrd)]
+impl std::ops::DerefMut for YamlDocument {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.root
+    }
+}
+
+/// How `YamlElement::merge_with` combines two sequences.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The overlay's sequence replaces the base's.
+    Replace,
+    /// The overlay's items are appended to the base's.
+    Concat,
+}
+
+/// One step of the path `YamlElement::walk` passes to its callback and
+/// `YamlDocument::resolve_all_annotated` reports.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub enum YamlElement {
-    Scalar(String, Option<String>),
-    Map(YamlMap, Option<String>),
-    Set(YamlSet, Option<String>),
+    /// Value, tag, anchor, style and the tag a `ScalarResolver` gave it.
+    /// Block scalars hold their value after folding and chomping; on output
+    /// libyaml derives the chomping indicator from the trailing newlines, so
+    /// `|-`, `|+`, `>-` and `>+` survive a round trip. The resolved tag only
+    /// types the value for the accessors and is never written out.
+    Scalar(
+        String,
+        Option<String>,
+        Option<String>,
+        Option<ScalarStyle>,
+        Option<String>,
+    ),
+    /// Entries, tag and anchor. Keys are stringified; see `YamlMap`.
+    Map(YamlMap, Option<String>, Option<String>),
+    Set(YamlSet, Option<String>, Option<String>),
     Alias(String),
+    #[default]
     None,
 }
 
+/// A scalar typed as the serde and JSON support read it. Without them only
+/// the variant is looked at, to tell map keys apart.
+#[cfg_attr(not(any(feature = "serde", feature = "json")), allow(dead_code))]
+pub(crate) enum Typed<'a> {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    Str(&'a str),
+}
+
 impl YamlElement {
     pub fn as_scalar<T>(&self) -> Option<T>
     where
         T: FromStr,
     {
-        if let Self::Scalar(s, _) = self {
+        if let Self::Scalar(s, ..) = self {
             Some(s.parse().ok()?)
         } else {
             None
@@ -48,216 +348,2221 @@ impl YamlElement {
     }
 
     pub fn as_str(&self) -> Option<&str> {
-        if let Self::Scalar(s, _) = self {
+        if let Self::Scalar(s, ..) = self {
             Some(s)
         } else {
             None
         }
     }
 
+    /// The scalar's only character, if it consists of exactly one Unicode
+    /// scalar value; a letter followed by a combining mark is two.
+    pub fn as_char(&self) -> Option<char> {
+        let mut chars = self.as_str()?.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) => Some(c),
+            _ => None,
+        }
+    }
+
+    /// Falls back to `default` when the node is not a scalar or doesn't
+    /// parse.
+    pub fn as_scalar_or<T: FromStr>(&self, default: T) -> T {
+        self.as_scalar().unwrap_or(default)
+    }
+
+    pub fn as_str_or<'a>(&'a self, default: &'a str) -> &'a str {
+        self.as_str().unwrap_or(default)
+    }
+
+    /// Parses an enum-like scalar with `E`'s own `FromStr`, so matching is
+    /// exactly as strict as that implementation.
+    pub fn as_enum<E: FromStr>(&self) -> Option<E> {
+        self.as_scalar()
+    }
+
+    /// Finds the scalar among `variants` ignoring ASCII case and parses the
+    /// listed spelling, so `Chip` and `CHIP` both reach `E::from_str("chip")`.
+    pub fn as_enum_ci<E: FromStr>(&self, variants: &[&str]) -> Option<E> {
+        self.try_enum(variants).ok()
+    }
+
+    /// Like `as_enum_ci`, but a miss is an `UnknownVariant` error naming
+    /// the accepted variants.
+    pub fn try_enum<E: FromStr>(&self, variants: &[&str]) -> Result<E, YamlError> {
+        let unknown = || YamlError::UnknownVariant {
+            found: self.describe(),
+            variants: variants.iter().map(|v| v.to_string()).collect(),
+        };
+        let s = self.as_str().ok_or_else(unknown)?;
+        variants
+            .iter()
+            .find(|v| v.eq_ignore_ascii_case(s))
+            .and_then(|v| v.parse().ok())
+            .ok_or_else(unknown)
+    }
+
+    /// Recognizes the YAML 1.1 boolean vocabulary (`y`, `yes`, `true`, `on`
+    /// and their negations), ignoring case.
+    pub fn as_bool(&self) -> Option<bool> {
+        let s = self.typed_str(&["bool"])?;
+        if ["y", "yes", "true", "on"]
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(s))
+        {
+            Some(true)
+        } else if ["n", "no", "false", "off"]
+            .iter()
+            .any(|t| t.eq_ignore_ascii_case(s))
+        {
+            Some(false)
+        } else {
+            None
+        }
+    }
+
+    /// Accepts the YAML 1.1 integer forms: decimal, `0x` hexadecimal, `0o`
+    /// or leading-zero octal and `0b` binary, with `_` separators. When a
+    /// `ScalarResolver` such as `CoreSchema` typed the scalar, leading zeros
+    /// are decimal, as YAML 1.2 reads them.
+    pub fn as_i64(&self) -> Option<i64> {
+        i64::try_from(self.parse_int(self.typed_str(&["int"])?)?).ok()
+    }
+
+    /// Accepts the same forms as `as_i64`.
+    pub fn as_u64(&self) -> Option<u64> {
+        u64::try_from(self.parse_int(self.typed_str(&["int"])?)?).ok()
+    }
+
+    fn parse_int(&self, s: &str) -> Option<i128> {
+        parse_int(s, self.resolved_tag().is_none())
+    }
+
+    /// Also recognizes the YAML special values `.inf`, `-.inf` and `.nan`,
+    /// and integers in any of the forms `as_i64` accepts.
+    pub fn as_f64(&self) -> Option<f64> {
+        let s = without_separators(self.typed_str(&["float", "int"])?);
+        if let Some(n) = self.parse_int(&s) {
+            return Some(n as f64);
+        }
+        let (sign, digits) = match s.strip_prefix('-') {
+            Some(digits) => (-1.0, digits),
+            None => (1.0, s.strip_prefix('+').unwrap_or(&s)),
+        };
+        match digits {
+            ".inf" | ".Inf" | ".INF" => Some(sign * f64::INFINITY),
+            ".nan" | ".NaN" | ".NAN" if digits.len() == s.len() => Some(f64::NAN),
+            _ if digits
+                .chars()
+                .all(|c| c.is_ascii_digit() || ".eE+-".contains(c)) =>
+            {
+                s.parse().ok()
+            }
+            _ => None,
+        }
+    }
+
+    /// True for the `None` variant, for scalars tagged or resolved as
+    /// `!!null`, and for other plain scalars spelled `~`, `null`, `Null`,
+    /// `NULL` or left empty. Quoted scalars are never null. The typed
+    /// accessors such as `as_i64` and `as_bool` are `None` for null nodes.
+    pub fn is_null(&self) -> bool {
+        match self {
+            Self::None => true,
+            Self::Scalar(..) if self.type_tag().is_some() => tag_matches(self.type_tag(), "!!null"),
+            Self::Scalar(s, _, _, style, _) => {
+                matches!(style, None | Some(ScalarStyle::Plain))
+                    && matches!(s.as_str(), "" | "~" | "null" | "Null" | "NULL")
+            }
+            _ => false,
+        }
+    }
+
+    /// Accepts both the full form of a tag and its `!!` shorthand, so
+    /// `is_tagged_as("!!int")` matches `tag:yaml.org,2002:int`. Only the
+    /// tag from the source counts; see `resolved_tag`.
+    pub fn is_tagged_as(&self, tag: &str) -> bool {
+        tag_matches(self.get_tag(), tag)
+    }
+
+    /// The tag the builder's `ScalarResolver` gave an untagged plain
+    /// scalar.
+    pub fn resolved_tag(&self) -> Option<&str> {
+        match self {
+            Self::Scalar(.., resolved) => resolved.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// The tag that types the node: its own, or else the resolved one.
+    fn type_tag(&self) -> Option<&str> {
+        self.get_tag().or_else(|| self.resolved_tag())
+    }
+
+    /// Runs `parse` on the text of a scalar tagged `tag`, matched as by
+    /// `is_tagged_as`. Any other node is `None`.
+    pub fn as_with_tag<T>(&self, tag: &str, parse: impl Fn(&str) -> Option<T>) -> Option<T> {
+        match self {
+            Self::Scalar(s, ..) if self.is_tagged_as(tag) => parse(s),
+            _ => None,
+        }
+    }
+
+    /// The scalar text, unless the node is null or a standard YAML tag,
+    /// given or resolved, pins it to a type other than one of `kinds`.
+    /// Custom tags don't restrict the conversion.
+    fn typed_str(&self, kinds: &[&str]) -> Option<&str> {
+        let kind = self
+            .type_tag()
+            .and_then(|t| t.strip_prefix(YAML_TAG_PREFIX));
+        match self {
+            _ if self.is_null() => None,
+            Self::Scalar(..) if kind.is_some_and(|kind| !kinds.contains(&kind)) => None,
+            Self::Scalar(s, ..) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Standard tags pick the type, and untagged quoted or block scalars
+    /// stay strings; other scalars take the first of null, bool, integer
+    /// and float that the typed accessors accept. `None` for collections
+    /// and aliases.
+    pub(crate) fn typed_scalar(&self) -> Option<Typed<'_>> {
+        Some(match self {
+            Self::Scalar(s, None, _, Some(style), _) if *style != ScalarStyle::Plain => {
+                Typed::Str(s)
+            }
+            _ if self.is_null() => Typed::Null,
+            Self::Scalar(s, ..) => {
+                if let Some(b) = self.as_bool() {
+                    Typed::Bool(b)
+                } else if let Some(n) = self.as_i64() {
+                    Typed::Int(n)
+                } else if let Some(n) = self.as_u64() {
+                    Typed::UInt(n)
+                } else if let Some(n) = self.as_f64() {
+                    Typed::Float(n)
+                } else {
+                    Typed::Str(s)
+                }
+            }
+            _ => return None,
+        })
+    }
+
     pub fn as_map(&self) -> Option<&YamlMap> {
-        if let Self::Map(map, _) = self {
+        if let Self::Map(map, ..) = self {
             Some(map)
         } else {
             None
         }
     }
 
+    pub fn as_map_mut(&mut self) -> Option<&mut YamlMap> {
+        if let Self::Map(map, ..) = self {
+            Some(map)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&YamlElement> {
+        self.as_map()?.get(key).map(AsRef::as_ref)
+    }
+
+    /// Like `get`, but ignores ASCII case. When several keys differ only by
+    /// case, the first in source order wins, even over an exact match.
+    pub fn get_ci(&self, key: &str) -> Option<&YamlElement> {
+        self.iter_map()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v)
+    }
+
+    pub fn get_mut(&mut self, key: &str) -> Option<&mut YamlElement> {
+        self.as_map_mut()?.get_mut(key).map(AsMut::as_mut)
+    }
+
+    /// Moves the value of `key` out of a map without cloning it, leaving
+    /// `None` in its place so the key and the order of the map are kept.
+    pub fn take(&mut self, key: &str) -> Option<YamlElement> {
+        self.get_mut(key).map(mem::take)
+    }
+
+    /// Moves an item out of a sequence, shifting the later ones down. An
+    /// index past the end is `None` rather than a panic.
+    pub fn remove(&mut self, index: usize) -> Option<YamlElement> {
+        match self {
+            Self::Set(set, ..) if index < set.len() => Some(*set.remove(index)),
+            _ => None,
+        }
+    }
+
+    /// Drops the map entries `keep` rejects, keeping the order of the rest.
+    /// With `recursive`, maps nested anywhere below are pruned too, before
+    /// their parent. Other nodes are left alone.
+    pub fn retain(&mut self, recursive: bool, keep: impl Fn(&str, &YamlElement) -> bool) {
+        let mut prune = |el: &mut YamlElement| {
+            if let Self::Map(map, ..) = el {
+                map.retain(|k, v| keep(k, v));
+            }
+            Ok(())
+        };
+        if recursive {
+            let _ = self.try_for_each_mut(&mut prune);
+        } else {
+            let _ = prune(self);
+        }
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.as_map().is_some_and(|map| map.contains_key(key))
+    }
+
+    /// Walks a slash-delimited path such as `chip/ports/0/name`. Numeric
+    /// segments index into sequences, anything else is a map key.
+    pub fn pointer(&self, path: &str) -> Option<&YamlElement> {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .try_fold(self, |el, segment| match segment.parse::<usize>() {
+                Ok(index) => match el {
+                    YamlElement::Set(set, ..) => set.get(index).map(AsRef::as_ref),
+                    _ => None,
+                },
+                Err(_) => el.get(segment),
+            })
+    }
+
+    /// Whether `pointer` finds a node at `path`.
+    pub fn has_pointer(&self, path: &str) -> bool {
+        self.pointer(path).is_some()
+    }
+
+    /// A copy of the items of a sequence; any other node, including a
+    /// missing one, is `None`. This used to give an empty vector for the
+    /// `None` variant; callers relying on that should use `as_vec_or_empty`.
     pub fn as_vec(&self) -> Option<YamlSet> {
-        if let Self::Set(map, _) = self {
-            Some(map.clone())
-        } else if self == &Self::None {
+        self.as_vec_ref().cloned()
+    }
+
+    /// Like `as_vec`, but a null or missing node is an empty sequence.
+    pub fn as_vec_or_empty(&self) -> Option<YamlSet> {
+        if self.is_null() {
             Some(YamlSet::new())
+        } else {
+            self.as_vec()
+        }
+    }
+
+    pub fn as_vec_ref(&self) -> Option<&YamlSet> {
+        if let Self::Set(set, ..) = self {
+            Some(set)
+        } else {
+            None
+        }
+    }
+
+    pub fn first(&self) -> Option<&YamlElement> {
+        self.as_vec_ref()?.first().map(AsRef::as_ref)
+    }
+
+    pub fn last(&self) -> Option<&YamlElement> {
+        self.as_vec_ref()?.last().map(AsRef::as_ref)
+    }
+
+    /// Parses every item of a sequence, failing if any of them is not a
+    /// scalar or doesn't parse.
+    pub fn as_vec_of<T: FromStr>(&self) -> Option<Vec<T>> {
+        if let Self::Set(set, ..) = self {
+            set.iter().map(|el| el.as_scalar()).collect()
         } else {
             None
         }
     }
 
-    pub fn get_tag(&self) -> Option<String> {
+    /// Parses every value of a map, failing if any of them is not a scalar
+    /// or doesn't parse.
+    pub fn as_map_of<T: FromStr>(&self) -> Option<BTreeMap<String, T>> {
+        self.as_map()?
+            .iter()
+            .map(|(k, v)| Some((k.clone(), v.as_scalar()?)))
+            .collect()
+    }
+
+    /// The members of a map tagged `!!set`, whose values must all be null.
+    pub fn as_yaml_set(&self) -> Option<BTreeSet<&str>> {
+        if !self.is_tagged_as("!!set") {
+            return None;
+        }
+        self.as_map()?
+            .iter()
+            .map(|(k, v)| if v.is_null() { Some(k.as_str()) } else { None })
+            .collect()
+    }
+
+    /// The pairs of a sequence tagged `!!omap`, in order. Every item must be
+    /// a map with exactly one entry.
+    pub fn as_omap(&self) -> Option<Vec<(&str, &YamlElement)>> {
+        if !self.is_tagged_as("!!omap") {
+            return None;
+        }
+        self.as_vec_ref()?
+            .iter()
+            .map(|item| match item.as_map() {
+                Some(map) if map.len() == 1 => {
+                    map.iter().next().map(|(k, v)| (k.as_str(), v.as_ref()))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Collects the non-sequence nodes beneath nested sequences in order.
+    /// Maps are not descended into, and any other node yields itself.
+    pub fn flatten(&self) -> Vec<&YamlElement> {
         match self {
-            YamlElement::Scalar(_, s) | YamlElement::Map(_, s) | YamlElement::Set(_, s) => {
-                s.clone()
-            }
-            YamlElement::Alias(_) | &YamlElement::None => None,
+            Self::Set(set, ..) => set.iter().flat_map(|el| el.flatten()).collect(),
+            el => vec![el],
         }
     }
-}
 
-impl YamlDocument {
-    pub fn new<'a>(path: impl Into<&'a str>) -> Option<Self> {
-        let file = File::open(path.into()).ok()?;
-        let parser = Parser::new(file).ok()?;
-        let iter = &mut parser.into_iter().peekable();
-        let mut s = Self {
-            root: vec![],
-            anchor: BTreeMap::new(),
+    /// Number of sequence items or map entries. Scalars, aliases and `None`
+    /// have no children and report 0.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Map(map, ..) => map.len(),
+            Self::Set(set, ..) => set.len(),
+            _ => 0,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn iter_seq(&self) -> impl Iterator<Item = &YamlElement> {
+        let set = match self {
+            Self::Set(set, ..) => set.as_slice(),
+            _ => &[],
         };
-        while let Some(Ok(i)) = iter.peek() {
-            println!("{:?}", i);
-            match i {
-                Event::StreamStart { .. } => {
-                    iter.next();
-                }
-                Event::DocumentStart { .. } => {
-                    iter.next();
-                }
-                Event::MappingStart { .. } => {
-                    let map = s.map(iter)?;
-                    s.root.push(map);
-                    iter.next();
-                }
-                Event::SequenceStart { .. } => {
-                    let vec = s.sequence(iter)?;
-                    s.root.push(vec);
-                    iter.next();
-                }
-                Event::DocumentEnd { .. } => {
-                    iter.next();
-                }
-                Event::StreamEnd => {
-                    //self.resolve_alias();
-                    return Some(s);
-                }
-                _ => unreachable!("{:?}", i),
-            }
+        set.iter().map(AsRef::as_ref)
+    }
+
+    pub fn iter_map(&self) -> impl Iterator<Item = (&str, &YamlElement)> {
+        self.as_map()
+            .into_iter()
+            .flatten()
+            .map(|(k, v)| (k.as_str(), v.as_ref()))
+    }
+
+    /// The full tag, with `!!` and any `%TAG` handle already expanded.
+    pub fn get_tag(&self) -> Option<&str> {
+        match self {
+            YamlElement::Scalar(_, s, ..)
+            | YamlElement::Map(_, s, _)
+            | YamlElement::Set(_, s, _) => s.as_deref(),
+            YamlElement::Alias(_) | &YamlElement::None => None,
         }
-        None
     }
 
-    pub fn resolve_alias(&self, alias: &YamlElement) -> Option<BoxedYamlElement> {
-        if let YamlElement::Alias(alias) = alias {
-            if self.anchor.contains_key(alias) {
-                return Some(self.anchor[alias].clone());
+    /// The name the node was anchored with. Expanded alias copies have
+    /// none.
+    pub fn get_anchor(&self) -> Option<&str> {
+        match self {
+            YamlElement::Scalar(_, _, s, ..)
+            | YamlElement::Map(_, _, s)
+            | YamlElement::Set(_, _, s) => s.as_deref(),
+            YamlElement::Alias(_) | &YamlElement::None => None,
+        }
+    }
+
+    /// The tag with standard YAML tags abbreviated to their `!!` form, so
+    /// only those allocate; local and custom tags are borrowed as is.
+    pub fn short_tag(&self) -> Option<Cow<'_, str>> {
+        let tag = self.get_tag()?;
+        Some(match tag.strip_prefix(YAML_TAG_PREFIX) {
+            Some(kind) => Cow::Owned(format!("!!{}", kind)),
+            None => Cow::Borrowed(tag),
+        })
+    }
+
+    /// Compares values and structure only, ignoring tags, anchors, scalar
+    /// styles and map key order. Aliases are equal when they name the same
+    /// anchor; their targets only take part once `resolve_all` has run.
+    pub fn structural_eq(&self, other: &YamlElement) -> bool {
+        match (self, other) {
+            (Self::Scalar(a, ..), Self::Scalar(b, ..)) => a == b,
+            (Self::Map(a, ..), Self::Map(b, ..)) => {
+                a.len() == b.len()
+                    && a.iter()
+                        .all(|(k, v)| b.get(k).is_some_and(|w| v.structural_eq(w)))
+            }
+            (Self::Set(a, ..), Self::Set(b, ..)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(v, w)| v.structural_eq(w))
             }
+            (Self::Alias(a), Self::Alias(b)) => a == b,
+            (Self::None, Self::None) => true,
+            _ => false,
         }
-        None
     }
 
-    fn scalar(&mut self, iter: &mut Peekable<ParserIter>) -> Option<BoxedYamlElement> {
-        if let Some(Ok(Event::Scalar {
-            value, anchor, tag, ..
-        })) = iter.peek()
-        {
-            let scalar = Box::new(YamlElement::Scalar(value.clone(), tag.clone()));
-            if let Some(anchor) = anchor {
-                self.anchor.insert(anchor.clone(), scalar.clone());
+    /// Compares two sequences as multisets: each item of one must pair with
+    /// a distinct `structural_eq` item of the other, so repeats count. Any
+    /// other pair of nodes compares as by `structural_eq`.
+    pub fn set_eq(&self, other: &YamlElement) -> bool {
+        match (self, other) {
+            (Self::Set(a, ..), Self::Set(b, ..)) => {
+                let mut unmatched: Vec<&YamlElement> = b.iter().map(AsRef::as_ref).collect();
+                a.len() == b.len()
+                    && a.iter().all(
+                        |v| match unmatched.iter().position(|w| v.structural_eq(w)) {
+                            Some(i) => {
+                                unmatched.swap_remove(i);
+                                true
+                            }
+                            None => false,
+                        },
+                    )
             }
-            return Some(scalar);
+            _ => self.structural_eq(other),
         }
-        None
     }
 
-    fn sequence(&mut self, iter: &mut Peekable<ParserIter>) -> Option<BoxedYamlElement> {
-        let el = iter.next()?.ok()?;
-        let mut root = YamlSet::new();
-        while let Some(Ok(i)) = iter.peek() {
-            println!("{:?}", i);
-            match i {
-                Event::Scalar { .. } => {
-                    root.push(self.scalar(iter)?);
-                    iter.next();
-                }
-                Event::SequenceStart { .. } => {
-                    root.push(self.sequence(iter)?);
-                    iter.next();
-                }
-                Event::MappingStart { .. } => {
-                    root.push(self.map(iter)?);
-                    iter.next();
-                }
-                Event::Alias { anchor } => {
-                    root.push(Box::new(YamlElement::Alias(anchor.clone())));
-                    iter.next();
-                }
-                Event::SequenceEnd => {
-                    if let Event::SequenceStart { anchor, tag, .. } = el {
-                        let root = Box::new(YamlElement::Set(root, tag));
-                        if let Some(anchor) = anchor {
-                            self.anchor.insert(anchor, root.clone());
-                        }
-                        return Some(root);
-                    }
-                    unreachable!()
+    /// Deep-merges `overlay` into a copy of `self` with sequences replaced;
+    /// see `merge_with`.
+    pub fn merge(&self, overlay: &YamlElement) -> YamlElement {
+        self.merge_with(overlay, MergeStrategy::Replace)
+    }
+
+    /// Maps merge key by key, keeping the base's key order and appending keys
+    /// only the overlay has. Two sequences combine according to `strategy`.
+    /// Any other pairing takes the overlay's node, and merged collections
+    /// take the overlay's tag and anchor when it has them.
+    pub fn merge_with(&self, overlay: &YamlElement, strategy: MergeStrategy) -> YamlElement {
+        match (self, overlay) {
+            (Self::Map(base, tag, anchor), Self::Map(over, otag, oanchor)) => {
+                let mut merged = base.clone();
+                for (k, v) in over {
+                    let v = match base.get(k) {
+                        Some(b) => b.merge_with(v, strategy),
+                        None => (**v).clone(),
+                    };
+                    merged.insert(k.clone(), Box::new(v));
                 }
-                _ => unreachable!("{:?}", i),
+                Self::Map(
+                    merged,
+                    otag.clone().or_else(|| tag.clone()),
+                    oanchor.clone().or_else(|| anchor.clone()),
+                )
             }
+            (Self::Set(base, tag, anchor), Self::Set(over, otag, oanchor))
+                if strategy == MergeStrategy::Concat =>
+            {
+                Self::Set(
+                    base.iter().chain(over).cloned().collect(),
+                    otag.clone().or_else(|| tag.clone()),
+                    oanchor.clone().or_else(|| anchor.clone()),
+                )
+            }
+            (_, overlay) => overlay.clone(),
         }
-        None
     }
 
-    fn map(&mut self, iter: &mut Peekable<ParserIter>) -> Option<BoxedYamlElement> {
-        let el = iter.next()?.ok()?;
-        let mut map = YamlMap::new();
-        let mut is_key = true;
-        let mut key = None;
-        while let Some(Ok(i)) = iter.peek() {
-            println!("{:?}", i);
-            match i {
-                Event::Scalar { .. } => {
-                    println!("{} {:?}", is_key, key);
-                    if is_key {
-                        if let YamlElement::Scalar(value, _) = *self.scalar(iter)? {
-                            key = Some(value);
-                            is_key = false
-                        } else {
-                            return None;
-                        }
-                    } else {
-                        map.insert(key.clone()?, self.scalar(iter)?);
-                        is_key = true;
-                    }
-                    iter.next();
-                }
-                Event::MappingStart { .. } => {
-                    map.insert(key.clone()?, self.map(iter)?);
-                    is_key = true;
-                    iter.next();
-                }
-                Event::SequenceStart { .. } => {
-                    map.insert(key.clone()?, self.sequence(iter)?);
-                    is_key = true;
-                    iter.next();
-                }
-                Event::Alias { anchor } => {
-                    map.insert(key.clone()?, Box::new(YamlElement::Alias(anchor.clone())));
-                    is_key = true;
-                    iter.next();
-                }
-                Event::MappingEnd => {
-                    if let Event::MappingStart { anchor, tag, .. } = el {
-                        let map = Box::new(YamlElement::Map(map, tag));
-                        if let Some(anchor) = anchor {
-                            self.anchor.insert(anchor, map.clone());
-                        }
-                        return Some(map);
-                    }
-                    unreachable!()
+    /// A `TypeMismatch` saying this node was found where `expected` was
+    /// wanted, as `FromYaml` implementations report it.
+    pub fn mismatch(&self, expected: &'static str) -> YamlError {
+        YamlError::TypeMismatch {
+            expected,
+            found: self.describe(),
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Scalar(s, ..) => format!("scalar `{}`", s),
+            Self::Map(..) => "map".to_string(),
+            Self::Set(..) => "sequence".to_string(),
+            Self::Alias(alias) => format!("alias `*{}`", alias),
+            Self::None => "none".to_string(),
+        }
+    }
+
+    /// Visits every node depth-first, parents before children, along with
+    /// the path leading to it from `self`, which gets an empty path.
+    pub fn walk(&self, mut f: impl FnMut(&[PathSegment], &YamlElement)) {
+        self.walk_inner(&mut vec![], &mut f);
+    }
+
+    fn walk_inner<F>(&self, path: &mut Vec<PathSegment>, f: &mut F)
+    where
+        F: FnMut(&[PathSegment], &YamlElement),
+    {
+        f(path, self);
+        match self {
+            Self::Map(map, ..) => {
+                for (k, v) in map {
+                    path.push(PathSegment::Key(k.clone()));
+                    v.walk_inner(path, f);
+                    path.pop();
+                }
+            }
+            Self::Set(set, ..) => {
+                for (i, v) in set.iter().enumerate() {
+                    path.push(PathSegment::Index(i));
+                    v.walk_inner(path, f);
+                    path.pop();
                 }
-                _ => unreachable!("{:?}", i),
             }
+            _ => {}
         }
-        None
     }
-}
 
-impl Index<&str> for YamlElement {
-    type Output = YamlElement;
+    /// Every node under `self`, itself included, that satisfies `pred`, in
+    /// document order. Aliases are matched as aliases; load with
+    /// `resolve_aliases` or call `resolve_all` to search their targets.
+    pub fn find_all(&self, pred: impl Fn(&YamlElement) -> bool) -> Vec<&YamlElement> {
+        let mut found = vec![];
+        self.for_each(&mut |el| {
+            if pred(el) {
+                found.push(el);
+            }
+        });
+        found
+    }
+
+    /// An indented, variant-named rendering for debugging. Anchors and
+    /// styles are left out; tags follow the node they belong to.
+    pub fn pretty(&self) -> String {
+        let mut out = String::new();
+        self.pretty_inner(&mut out, 0);
+        out
+    }
 
-    fn index(&self, index: &str) -> &Self::Output {
-        match self.as_map() {
-            Some(map) if map.contains_key(index) => map[index].as_ref(),
-            _ => &Self::None,
+    fn pretty_inner(&self, out: &mut String, depth: usize) {
+        let tag = |out: &mut String, tag: &Option<String>| {
+            if let Some(tag) = tag {
+                out.push(' ');
+                out.push_str(tag);
+            }
+        };
+        let indent = "  ".repeat(depth + 1);
+        match self {
+            Self::Scalar(s, t, ..) => {
+                out.push_str(&format!("Scalar({:?})", s));
+                tag(out, t);
+            }
+            Self::Map(map, t, _) => {
+                out.push_str("Map");
+                tag(out, t);
+                if map.is_empty() {
+                    return out.push_str(" {}");
+                }
+                out.push_str(" {\n");
+                for (k, v) in map {
+                    out.push_str(&format!("{}{:?}: ", indent, k));
+                    v.pretty_inner(out, depth + 1);
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push('}');
+            }
+            Self::Set(set, t, _) => {
+                out.push_str("Set");
+                tag(out, t);
+                if set.is_empty() {
+                    return out.push_str(" []");
+                }
+                out.push_str(" [\n");
+                for v in set {
+                    out.push_str(&indent);
+                    v.pretty_inner(out, depth + 1);
+                    out.push('\n');
+                }
+                out.push_str(&"  ".repeat(depth));
+                out.push(']');
+            }
+            Self::Alias(alias) => out.push_str(&format!("Alias({:?})", alias)),
+            Self::None => out.push_str("None"),
         }
     }
-}
 
-impl Index<String> for YamlElement {
-    type Output = YamlElement;
+    /// A deep copy with every tag removed.
+    pub fn untagged(&self) -> YamlElement {
+        let mut el = self.clone();
+        el.strip_tags();
+        el
+    }
+
+    /// Removes every tag, resolved ones included.
+    pub fn strip_tags(&mut self) {
+        let _ = self.try_for_each_mut(&mut |el| {
+            if let Self::Scalar(.., resolved) = el {
+                *resolved = None;
+            }
+            if let Self::Scalar(_, tag, ..) | Self::Map(_, tag, _) | Self::Set(_, tag, _) = el {
+                *tag = None;
+            }
+            Ok(())
+        });
+    }
 
-    fn index(&self, index: String) -> &Self::Output {
-        match self.as_map() {
-            Some(map) if map.contains_key(&index) => map[&index].as_ref(),
-            _ => &Self::None,
+    /// Visits the node, then its children in order.
+    fn for_each<'a, F>(&'a self, f: &mut F)
+    where
+        F: FnMut(&'a YamlElement),
+    {
+        f(self);
+        match self {
+            Self::Map(map, ..) => map.values().for_each(|v| v.for_each(f)),
+            Self::Set(set, ..) => set.iter().for_each(|v| v.for_each(f)),
+            _ => {}
         }
     }
+
+    fn try_for_each_mut<F>(&mut self, f: &mut F) -> Result<(), YamlError>
+    where
+        F: FnMut(&mut YamlElement) -> Result<(), YamlError>,
+    {
+        match self {
+            Self::Map(map, ..) => map.values_mut().try_for_each(|v| v.try_for_each_mut(f))?,
+            Self::Set(set, ..) => set.iter_mut().try_for_each(|v| v.try_for_each_mut(f))?,
+            _ => {}
+        }
+        f(self)
+    }
+
+    /// Drops the anchor of this node and of every node inside it, for
+    /// copies that must not redefine the anchors of their original.
+    fn without_anchors(mut self) -> Self {
+        let _ = self.try_for_each_mut(&mut |el| {
+            if let Self::Scalar(_, _, anchor, ..)
+            | Self::Map(_, _, anchor)
+            | Self::Set(_, _, anchor) = el
+            {
+                *anchor = None;
+            }
+            Ok(())
+        });
+        self
+    }
+
+    /// The number of nodes in this subtree, itself included.
+    fn node_count(&self) -> usize {
+        let mut nodes = 0;
+        self.for_each(&mut |_| nodes += 1);
+        nodes
+    }
+
+    /// The number of collections on the deepest path through the node.
+    fn height(&self) -> usize {
+        match self {
+            Self::Map(map, ..) => 1 + map.values().map(|v| v.height()).max().unwrap_or(0),
+            Self::Set(set, ..) => 1 + set.iter().map(|v| v.height()).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+
+    /// Records each anchored node by its anchor, the last one to finish
+    /// winning as it does in the anchor table.
+    fn anchored_nodes<'a>(&'a self, found: &mut BTreeMap<&'a str, &'a YamlElement>) {
+        match self {
+            Self::Map(map, ..) => map.values().for_each(|v| v.anchored_nodes(found)),
+            Self::Set(set, ..) => set.iter().for_each(|v| v.anchored_nodes(found)),
+            _ => {}
+        }
+        if let Some(anchor) = self.get_anchor() {
+            found.insert(anchor, self);
+        }
+    }
+
+    /// Streams the element as YAML, formatted like `Display`. The writer is
+    /// not flushed.
+    pub fn to_writer(&self, writer: impl io::Write) -> io::Result<()> {
+        self.write_yaml(writer).map_err(into_io_error)
+    }
+
+    /// The element as YAML, formatted like `Display`, or an `Io` error for
+    /// an anchor, alias or tag libyaml can't write.
+    pub fn to_yaml_string(&self) -> Result<String, YamlError> {
+        let mut buf = vec![];
+        self.to_writer(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    /// A copy without the anchors, aliases and tags `to_yaml_string` would
+    /// reject.
+    fn writable(&self) -> YamlElement {
+        let name = |s: &str| {
+            !s.is_empty()
+                && s.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        };
+        let mut copy = self.clone();
+        let _ = copy.try_for_each_mut(&mut |el| {
+            match el {
+                Self::Scalar(_, tag, anchor, ..)
+                | Self::Map(_, tag, anchor)
+                | Self::Set(_, tag, anchor) => {
+                    if anchor.as_deref().is_some_and(|a| !name(a)) {
+                        *anchor = None;
+                    }
+                    if tag.as_deref() == Some("") {
+                        *tag = None;
+                    }
+                }
+                Self::Alias(alias) if !name(alias) => *el = Self::None,
+                _ => {}
+            }
+            Ok(())
+        });
+        copy
+    }
+
+    fn write_yaml(&self, writer: impl io::Write) -> Result<(), EmitterError> {
+        write_document(writer, self, None, vec![], true, true)
+    }
+
+    fn emit(&self, emitter: &mut Emitter) -> Result<(), EmitterError> {
+        match self {
+            Self::Scalar(value, tag, anchor, style, _) => emitter.emit(Event::Scalar {
+                anchor: anchor.clone(),
+                tag: tag.clone(),
+                value: value.clone(),
+                plain_implicit: tag.is_none(),
+                quoted_implicit: tag.is_none(),
+                style: *style,
+            }),
+            Self::Map(map, tag, anchor) => {
+                emitter.emit(Event::MappingStart {
+                    anchor: anchor.clone(),
+                    tag: tag.clone(),
+                    implicit: tag.is_none(),
+                    style: None,
+                })?;
+                // Without a style libyaml quotes any key that wouldn't read
+                // back as the same text, so keys round-trip as strings.
+                for (key, value) in map {
+                    Self::Scalar(key.clone(), None, None, None, None).emit(emitter)?;
+                    value.emit(emitter)?;
+                }
+                emitter.emit(Event::MappingEnd)
+            }
+            Self::Set(set, tag, anchor) => {
+                emitter.emit(Event::SequenceStart {
+                    anchor: anchor.clone(),
+                    tag: tag.clone(),
+                    implicit: tag.is_none(),
+                    style: None,
+                })?;
+                for value in set {
+                    value.emit(emitter)?;
+                }
+                emitter.emit(Event::SequenceEnd)
+            }
+            Self::Alias(anchor) => emitter.emit(Event::Alias {
+                anchor: anchor.clone(),
+            }),
+            Self::None => Self::Scalar(String::new(), None, None, None, None).emit(emitter),
+        }
+    }
+}
+
+impl PartialOrd for YamlElement {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for YamlElement {
+    fn cmp(&self, other: &Self) -> Ordering {
+        fn sorted(map: &YamlMap) -> Vec<(&String, &BoxedYamlElement)> {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+        }
+
+        fn rank(el: &YamlElement) -> u8 {
+            match el {
+                YamlElement::Scalar(..) => 0,
+                YamlElement::Map(..) => 1,
+                YamlElement::Set(..) => 2,
+                YamlElement::Alias(_) => 3,
+                YamlElement::None => 4,
+            }
+        }
+
+        match (self, other) {
+            (Self::Scalar(a, at, aa, as_, ar), Self::Scalar(b, bt, ba, bs, br)) => {
+                (a, at, aa, as_, ar).cmp(&(b, bt, ba, bs, br))
+            }
+            (Self::Map(a, at, aa), Self::Map(b, bt, ba)) => {
+                (sorted(a), at, aa).cmp(&(sorted(b), bt, ba))
+            }
+            (Self::Set(a, at, aa), Self::Set(b, bt, ba)) => (a, at, aa).cmp(&(b, bt, ba)),
+            (Self::Alias(a), Self::Alias(b)) => a.cmp(b),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+}
+
+impl PartialEq<&str> for YamlElement {
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl PartialEq<i64> for YamlElement {
+    fn eq(&self, other: &i64) -> bool {
+        self.as_i64() == Some(*other)
+    }
+}
+
+impl PartialEq<bool> for YamlElement {
+    fn eq(&self, other: &bool) -> bool {
+        self.as_bool() == Some(*other)
+    }
+}
+
+/// Builds an untagged map; a repeated key keeps its first position and its
+/// last value.
+impl<K: Into<String>> iter::FromIterator<(K, YamlElement)> for YamlElement {
+    fn from_iter<T: IntoIterator<Item = (K, YamlElement)>>(iter: T) -> Self {
+        let map = iter
+            .into_iter()
+            .map(|(k, v)| (k.into(), Box::new(v)))
+            .collect();
+        Self::Map(map, None, None)
+    }
+}
+
+/// Builds an untagged sequence.
+impl iter::FromIterator<YamlElement> for YamlElement {
+    fn from_iter<T: IntoIterator<Item = YamlElement>>(iter: T) -> Self {
+        Self::Set(iter.into_iter().map(Box::new).collect(), None, None)
+    }
+}
+
+impl TryFrom<&YamlElement> for String {
+    type Error = YamlError;
+
+    fn try_from(el: &YamlElement) -> Result<Self, Self::Error> {
+        el.as_str()
+            .map(str::to_string)
+            .ok_or_else(|| el.mismatch("string"))
+    }
+}
+
+impl TryFrom<&YamlElement> for i64 {
+    type Error = YamlError;
+
+    fn try_from(el: &YamlElement) -> Result<Self, Self::Error> {
+        el.as_i64().ok_or_else(|| el.mismatch("integer"))
+    }
+}
+
+impl TryFrom<&YamlElement> for f64 {
+    type Error = YamlError;
+
+    fn try_from(el: &YamlElement) -> Result<Self, Self::Error> {
+        el.as_f64().ok_or_else(|| el.mismatch("float"))
+    }
+}
+
+impl TryFrom<&YamlElement> for bool {
+    type Error = YamlError;
+
+    fn try_from(el: &YamlElement) -> Result<Self, Self::Error> {
+        el.as_bool().ok_or_else(|| el.mismatch("boolean"))
+    }
+}
+
+/// Callbacks for `YamlDocument::stream`. Map keys and values arrive as
+/// alternating nodes between `on_mapping_start` and `on_mapping_end`. Every
+/// callback defaults to doing nothing, and an error stops the stream.
+#[allow(unused_variables)]
+pub trait YamlVisitor {
+    fn on_document_start(&mut self) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_document_end(&mut self) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_scalar(
+        &mut self,
+        value: &str,
+        tag: Option<&str>,
+        anchor: Option<&str>,
+    ) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_sequence_start(
+        &mut self,
+        tag: Option<&str>,
+        anchor: Option<&str>,
+    ) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_sequence_end(&mut self) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_mapping_start(
+        &mut self,
+        tag: Option<&str>,
+        anchor: Option<&str>,
+    ) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_mapping_end(&mut self) -> Result<(), YamlError> {
+        Ok(())
+    }
+
+    fn on_alias(&mut self, anchor: &str) -> Result<(), YamlError> {
+        Ok(())
+    }
+}
+
+pub trait FromYaml: Sized {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError>;
+}
+
+impl FromYaml for YamlElement {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        Ok(el.clone())
+    }
+}
+
+impl FromYaml for String {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        Self::try_from(el)
+    }
+}
+
+impl FromYaml for i64 {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        Self::try_from(el)
+    }
+}
+
+impl FromYaml for u64 {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        el.as_u64().ok_or_else(|| el.mismatch("unsigned integer"))
+    }
+}
+
+impl FromYaml for f64 {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        Self::try_from(el)
+    }
+}
+
+impl FromYaml for bool {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        Self::try_from(el)
+    }
+}
+
+impl<T: FromYaml> FromYaml for Option<T> {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        if el.is_null() {
+            Ok(None)
+        } else {
+            T::from_yaml(el).map(Some)
+        }
+    }
+}
+
+impl<T: FromYaml> FromYaml for Vec<T> {
+    fn from_yaml(el: &YamlElement) -> Result<Self, YamlError> {
+        match el {
+            YamlElement::Set(..) => el.iter_seq().map(T::from_yaml).collect(),
+            el => Err(el.mismatch("sequence")),
+        }
+    }
+}
+
+/// Emits the element as YAML, without the comments of the source. Names
+/// libyaml can't write, as anchors and aliases may only use letters, digits,
+/// `_` and `-`, are left out along with empty tags, and such an alias
+/// becomes an empty node; `to_yaml_string` reports them instead.
+impl fmt::Display for YamlElement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yaml = match self.to_yaml_string() {
+            Ok(yaml) => yaml,
+            Err(_) => self.writable().to_yaml_string().unwrap_or_default(),
+        };
+        f.write_str(&yaml)
+    }
+}
+
+impl YamlDocument {
+    /// Loads a file holding one document. A file with several `---`
+    /// documents is a `MultipleDocuments` error, where older versions put
+    /// each document's root side by side; `new_multi` loads those, one
+    /// `YamlDocument` per document.
+    pub fn new<'a>(path: impl Into<&'a str>) -> Result<Self, YamlError> {
+        YamlDocumentBuilder::new().build_from_file(path.into())
+    }
+
+    /// Loads a file with every alias resolved, so the tree holds no
+    /// dangling or cyclic aliases and respects the default depth and node
+    /// limits, or the first violation is returned.
+    pub fn new_checked<'a>(path: impl Into<&'a str>) -> Result<Self, YamlError> {
+        YamlDocumentBuilder::new()
+            .resolve_aliases(true)
+            .build_from_file(path.into())
+    }
+
+    pub fn new_multi<'a>(path: impl Into<&'a str>) -> Result<Vec<Self>, YamlError> {
+        Self::from_reader_multi(File::open(path.into())?)
+    }
+
+    /// Parses a gzip-compressed file. Content that isn't gzip is an `Io`
+    /// error.
+    #[cfg(feature = "gzip")]
+    pub fn from_gz_path<'a>(path: impl Into<&'a str>) -> Result<Self, YamlError> {
+        Self::from_reader(flate2::read::GzDecoder::new(File::open(path.into())?))
+    }
+
+    /// Parses a single document, as `new` does; see
+    /// `YamlDocumentBuilder::build_from_reader`.
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, YamlError> {
+        YamlDocumentBuilder::new().build_from_reader(reader)
+    }
+
+    /// Parses raw bytes without requiring them to be valid UTF-8 up front;
+    /// invalid input is reported by the parser. A leading byte order mark
+    /// selects UTF-8, UTF-16LE or UTF-16BE and is not part of the content.
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, YamlError> {
+        YamlDocumentBuilder::new().build_from_slice(bytes)
+    }
+
+    pub fn from_reader_multi(reader: impl io::Read) -> Result<Vec<Self>, YamlError> {
+        YamlDocumentBuilder::new().build_from_reader_multi(reader)
+    }
+
+    /// Feeds every event of the stream to `visitor` without building a
+    /// tree, so only the node being read is held in memory.
+    pub fn stream(reader: impl io::Read, visitor: &mut impl YamlVisitor) -> Result<(), YamlError> {
+        let iter = &mut Events::new(reader, None)?;
+        loop {
+            match iter.next()? {
+                Event::StreamStart { .. } => {}
+                Event::StreamEnd => return Ok(()),
+                Event::DocumentStart { .. } => visitor.on_document_start()?,
+                Event::DocumentEnd { .. } => visitor.on_document_end()?,
+                Event::Scalar {
+                    value, tag, anchor, ..
+                } => visitor.on_scalar(&value, tag.as_deref(), anchor.as_deref())?,
+                Event::SequenceStart { tag, anchor, .. } => {
+                    visitor.on_sequence_start(tag.as_deref(), anchor.as_deref())?
+                }
+                Event::SequenceEnd => visitor.on_sequence_end()?,
+                Event::MappingStart { tag, anchor, .. } => {
+                    visitor.on_mapping_start(tag.as_deref(), anchor.as_deref())?
+                }
+                Event::MappingEnd => visitor.on_mapping_end()?,
+                Event::Alias { anchor } => visitor.on_alias(&anchor)?,
+            }
+        }
+    }
+
+    /// The raw parser events of the stream, from `StreamStart` to
+    /// `StreamEnd`. Iteration stops after the first error.
+    pub fn events<'a>(
+        reader: impl io::Read + 'a,
+    ) -> Result<impl Iterator<Item = Result<Event, YamlError>> + 'a, YamlError> {
+        let mut iter = Events::new(reader, None)?;
+        let mut done = false;
+        Ok(iter::from_fn(move || {
+            if done {
+                return None;
+            }
+            let event = iter.next();
+            done = matches!(event, Ok(Event::StreamEnd) | Err(_));
+            Some(event)
+        }))
+    }
+
+    fn empty() -> Self {
+        Self {
+            root: vec![],
+            anchor: YamlMap::new(),
+            redefined: BTreeSet::new(),
+            duplicates: vec![],
+            version: None,
+            tags: vec![],
+            explicit_start: false,
+            explicit_end: false,
+            max_depth: MAX_DEPTH,
+            max_nodes: MAX_NODES,
+        }
+    }
+
+    fn document(
+        iter: &mut Events,
+        options: &YamlDocumentBuilder,
+        version: Option<VersionDirective>,
+        tags: Vec<TagDirective>,
+        implicit: bool,
+    ) -> Result<Self, YamlError> {
+        let mut s = Self {
+            version,
+            tags,
+            explicit_start: !implicit,
+            max_depth: options.max_depth,
+            max_nodes: options.max_nodes,
+            ..Self::empty()
+        };
+        while !matches!(iter.peek()?, Event::DocumentEnd { .. }) {
+            let node = s.node(iter, options, 0)?;
+            s.root.push(node);
+        }
+        if let Event::DocumentEnd { implicit } = iter.next()? {
+            s.explicit_end = !implicit;
+        }
+        s.bind_redefined()?;
+        Ok(s)
+    }
+
+    /// Replaces each alias to a redefined anchor with a copy of the
+    /// definition before it, which is the one YAML binds it to; the anchor
+    /// table only keeps the last. An alias with no definition before it is
+    /// left alone. The copies count against `max_nodes`.
+    fn bind_redefined(&mut self) -> Result<(), YamlError> {
+        let redefined = mem::take(&mut self.redefined);
+        if redefined.is_empty() {
+            return Ok(());
+        }
+        let mut budget = self.max_nodes;
+        let mut visible = BTreeMap::new();
+        let mut root = mem::take(&mut self.root);
+        let bound = root
+            .iter_mut()
+            .try_for_each(|el| self.bind(el, &redefined, &mut visible, &mut budget, 0));
+        self.root = root;
+        bound?;
+        // Scalars hold no aliases, and the anchors of keys, the only ones
+        // outside the tree, are scalars; the rest are copied back.
+        let mut anchored = BTreeMap::new();
+        for el in &self.root {
+            el.anchored_nodes(&mut anchored);
+        }
+        let anchored = anchored
+            .into_iter()
+            .filter(|(name, _)| {
+                !matches!(
+                    self.anchor.get(*name).map(AsRef::as_ref),
+                    Some(YamlElement::Scalar(..))
+                )
+            })
+            .map(|(name, el)| (name.to_string(), Box::new(el.clone())))
+            .collect::<Vec<_>>();
+        self.anchor.extend(anchored);
+        Ok(())
+    }
+
+    /// `visible` holds the latest finished definition of each redefined
+    /// anchor, with its height, and `depth` counts the collections
+    /// enclosing `element`.
+    fn bind(
+        &self,
+        element: &mut YamlElement,
+        redefined: &BTreeSet<String>,
+        visible: &mut BTreeMap<String, (YamlElement, usize)>,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<(), YamlError> {
+        match element {
+            YamlElement::Alias(alias) => {
+                if let Some((target, height)) = visible.get(alias.as_str()) {
+                    if *height > 0 {
+                        self.descend(depth + height - 1)?;
+                    }
+                    self.spend(budget, target.node_count())?;
+                    *element = target.clone();
+                }
+                return Ok(());
+            }
+            YamlElement::Map(map, ..) => {
+                for v in map.values_mut() {
+                    self.bind(v, redefined, visible, budget, depth + 1)?;
+                }
+            }
+            YamlElement::Set(set, ..) => {
+                for v in set {
+                    self.bind(v, redefined, visible, budget, depth + 1)?;
+                }
+            }
+            _ => {}
+        }
+        if let Some(anchor) = element.get_anchor().filter(|a| redefined.contains(*a)) {
+            let copy = element.clone().without_anchors();
+            let height = copy.height();
+            visible.insert(anchor.to_string(), (copy, height));
+        }
+        Ok(())
+    }
+
+    /// Runs `handler` on every node tagged `tag`, children before their
+    /// parents, in the document and in its anchor table. The handler may
+    /// rewrite the node in place or reject it by returning an error.
+    pub fn with_tag_handler<F>(mut self, tag: &str, mut handler: F) -> Result<Self, YamlError>
+    where
+        F: FnMut(&mut YamlElement, &str) -> Result<(), YamlError>,
+    {
+        let mut f = |el: &mut YamlElement| {
+            if el.is_tagged_as(tag) {
+                handler(el, tag)
+            } else {
+                Ok(())
+            }
+        };
+        for el in self.root.iter_mut().chain(self.anchor.values_mut()) {
+            el.try_for_each_mut(&mut f)?;
+        }
+        Ok(self)
+    }
+
+    /// Keys and values may hold any Unicode, but libyaml only accepts ASCII
+    /// letters, digits, `-` and `_` in anchor names.
+    pub fn anchors(&self) -> &YamlMap {
+        &self.anchor
+    }
+
+    /// The anchor `element` was declared with, if this document defines it.
+    /// The name is read from the node itself rather than found by comparing
+    /// it with the anchor table, so equal but unanchored nodes don't match.
+    pub fn anchor_name_of<'a>(&self, element: &'a YamlElement) -> Option<&'a str> {
+        element
+            .get_anchor()
+            .filter(|name| self.anchor.contains_key(*name))
+    }
+
+    /// The `%YAML` directive of the document, if it had one. The typed
+    /// accessors follow YAML 1.1 rules, as libyaml does, whatever it says;
+    /// only a `ScalarResolver` changes how plain scalars are typed.
+    pub fn yaml_version(&self) -> Option<(u32, u32)> {
+        self.version
+            .map(|VersionDirective(major, minor)| (major.into(), minor.into()))
+    }
+
+    /// Wraps the document for read-only sharing: clones of the `Arc` are
+    /// cheap, and the tree itself is never copied.
+    pub fn into_shared(self) -> Arc<Self> {
+        Arc::new(self)
+    }
+
+    /// The `%TAG` directives of the document. Tags on the nodes are already
+    /// expanded through them, so these only matter for writing it back.
+    pub fn tag_directives(&self) -> &[TagDirective] {
+        &self.tags
+    }
+
+    /// Whether the document opened with a `---` marker.
+    pub fn is_explicit(&self) -> bool {
+        self.explicit_start
+    }
+
+    /// Whether the document closed with a `...` marker.
+    pub fn is_explicit_end(&self) -> bool {
+        self.explicit_end
+    }
+
+    /// Writes the document back out with its `%YAML` and `%TAG` directives
+    /// and the document markers it was read with, so tags are written with
+    /// the same shorthands. The writer is not flushed.
+    pub fn to_writer(&self, writer: impl io::Write) -> io::Result<()> {
+        let root = self.root.first().map_or(&YamlElement::None, AsRef::as_ref);
+        write_document(
+            writer,
+            root,
+            self.version,
+            self.tags.clone(),
+            !self.explicit_start,
+            !self.explicit_end,
+        )
+        .map_err(into_io_error)
+    }
+
+    /// Keys that repeated within a map, with the position of each repeat.
+    /// Only recorded under `DuplicateKeys::Collect`.
+    pub fn duplicate_keys(&self) -> &[(String, Position)] {
+        &self.duplicates
+    }
+
+    /// A copy of the node `alias` names, tag and anchor included. Aliases
+    /// inside it are left as they are; see `resolve`.
+    pub fn resolve_alias(&self, alias: &YamlElement) -> Option<BoxedYamlElement> {
+        if let YamlElement::Alias(alias) = alias {
+            if self.anchor.contains_key(alias) {
+                return Some(self.anchor[alias].clone());
+            }
+        }
+        None
+    }
+
+    /// Drops the spare capacity of every sequence, map and string in the
+    /// document and its anchor table. Map keys can't be changed in place,
+    /// so they keep theirs.
+    pub fn shrink(&mut self) {
+        let shrink = |s: &mut Option<String>| {
+            if let Some(s) = s {
+                s.shrink_to_fit();
+            }
+        };
+        let mut compact = |el: &mut YamlElement| {
+            match el {
+                YamlElement::Scalar(value, tag, anchor, _, resolved) => {
+                    value.shrink_to_fit();
+                    shrink(tag);
+                    shrink(anchor);
+                    shrink(resolved);
+                }
+                YamlElement::Map(map, tag, anchor) => {
+                    map.shrink_to_fit();
+                    shrink(tag);
+                    shrink(anchor);
+                }
+                YamlElement::Set(set, tag, anchor) => {
+                    set.shrink_to_fit();
+                    shrink(tag);
+                    shrink(anchor);
+                }
+                YamlElement::Alias(alias) => alias.shrink_to_fit(),
+                YamlElement::None => {}
+            }
+            Ok(())
+        };
+        for el in self.root.iter_mut().chain(self.anchor.values_mut()) {
+            let _ = el.try_for_each_mut(&mut compact);
+        }
+        self.root.shrink_to_fit();
+        self.anchor.shrink_to_fit();
+        self.duplicates.shrink_to_fit();
+    }
+
+    /// Names of the aliases with no matching anchor in this document, each
+    /// listed once in the order first seen.
+    pub fn dangling_aliases(&self) -> Vec<String> {
+        let mut dangling = vec![];
+        for el in &self.root {
+            el.for_each(&mut |el| {
+                if let YamlElement::Alias(alias) = el {
+                    if !self.anchor.contains_key(alias) && !dangling.contains(alias) {
+                        dangling.push(alias.clone());
+                    }
+                }
+            });
+        }
+        dangling
+    }
+
+    /// Deep-clones `element`, replacing every alias with its resolved anchor
+    /// target. Each expanded copy keeps the target's tag but drops its
+    /// anchors, nested ones included. Copying more than `max_nodes` nodes
+    /// for aliases is an `ExpansionLimit` error. An alias to an anchor
+    /// missing from the document is an `UnknownAnchor` error, and an anchor
+    /// that contains an alias to itself is a `CyclicAlias` error.
+    pub fn resolve(&self, element: &YamlElement) -> Result<YamlElement, YamlError> {
+        let mut budget = self.max_nodes;
+        self.resolve_inner(element, &mut vec![], &mut budget, 0)
+    }
+
+    /// `budget` is the number of nodes alias expansions may still copy, and
+    /// `depth` counts the collections enclosing `element` once resolved.
+    fn resolve_inner<'a>(
+        &'a self,
+        element: &YamlElement,
+        expanding: &mut Vec<&'a str>,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<YamlElement, YamlError> {
+        if !expanding.is_empty() {
+            self.spend(budget, 1)?;
+        }
+        if matches!(element, YamlElement::Map(..) | YamlElement::Set(..)) {
+            self.descend(depth)?;
+        }
+        Ok(match element {
+            YamlElement::Alias(alias) => match self.anchor.get_key_value(alias) {
+                Some(_) if expanding.contains(&alias.as_str()) => {
+                    return Err(YamlError::CyclicAlias(alias.clone()))
+                }
+                Some((name, target)) => {
+                    expanding.push(name);
+                    let resolved = self.resolve_inner(target, expanding, budget, depth)?;
+                    expanding.pop();
+                    resolved.without_anchors()
+                }
+                None => return Err(YamlError::UnknownAnchor(alias.clone())),
+            },
+            YamlElement::Map(map, tag, anchor) => YamlElement::Map(
+                map.iter()
+                    .map(|(k, v)| {
+                        Ok((
+                            k.clone(),
+                            Box::new(self.resolve_inner(v, expanding, budget, depth + 1)?),
+                        ))
+                    })
+                    .collect::<Result<_, YamlError>>()?,
+                tag.clone(),
+                anchor.clone(),
+            ),
+            YamlElement::Set(set, tag, anchor) => YamlElement::Set(
+                set.iter()
+                    .map(|v| {
+                        Ok(Box::new(self.resolve_inner(
+                            v,
+                            expanding,
+                            budget,
+                            depth + 1,
+                        )?))
+                    })
+                    .collect::<Result<_, YamlError>>()?,
+                tag.clone(),
+                anchor.clone(),
+            ),
+            el => el.clone(),
+        })
+    }
+
+    /// Resolves every alias in place, as `resolve` does, with one
+    /// `max_nodes` budget for the whole document.
+    pub fn resolve_all(&mut self) -> Result<(), YamlError> {
+        let mut budget = self.max_nodes;
+        let root: YamlSet = self
+            .root
+            .iter()
+            .map(|el| {
+                Ok(Box::new(self.resolve_inner(
+                    el,
+                    &mut vec![],
+                    &mut budget,
+                    0,
+                )?))
+            })
+            .collect::<Result<_, YamlError>>()?;
+        // Copies drop their anchors, so the anchored nodes left in the
+        // resolved tree are the anchors' own and needn't be expanded again.
+        let mut resolved = BTreeMap::new();
+        for el in &root {
+            el.anchored_nodes(&mut resolved);
+        }
+        let anchor = self
+            .anchor
+            .iter()
+            .map(|(k, v)| {
+                let v = match resolved.get(k.as_str()) {
+                    Some(el) => (*el).clone(),
+                    None => self.resolve_inner(v, &mut vec![], &mut budget, 0)?,
+                };
+                Ok((k.clone(), Box::new(v)))
+            })
+            .collect::<Result<_, YamlError>>()?;
+        self.root = root;
+        self.anchor = anchor;
+        Ok(())
+    }
+
+    /// Fails when a collection at `depth` would nest too deeply.
+    fn descend(&self, depth: usize) -> Result<(), YamlError> {
+        if depth >= self.max_depth {
+            return Err(YamlError::DepthExceeded(None));
+        }
+        Ok(())
+    }
+
+    /// Takes `nodes` from `budget`, or fails once it would run out.
+    fn spend(&self, budget: &mut usize, nodes: usize) -> Result<(), YamlError> {
+        *budget = budget
+            .checked_sub(nodes)
+            .ok_or(YamlError::ExpansionLimit(self.max_nodes))?;
+        Ok(())
+    }
+
+    /// Resolves like `resolve_all` and reports where each alias was
+    /// expanded: the path to the node, starting with its root's index, and
+    /// the anchor it was copied from. Aliases inside an expanded anchor are
+    /// listed at the path they end up at, after the alias containing them.
+    pub fn resolve_all_annotated(&mut self) -> Result<Vec<(Vec<PathSegment>, String)>, YamlError> {
+        let mut found = vec![];
+        for (i, el) in self.root.iter().enumerate() {
+            self.expansions(el, &[PathSegment::Index(i)], &mut vec![], &mut found);
+        }
+        self.resolve_all()?;
+        Ok(found)
+    }
+
+    fn expansions<'a>(
+        &'a self,
+        el: &YamlElement,
+        prefix: &[PathSegment],
+        expanding: &mut Vec<&'a str>,
+        found: &mut Vec<(Vec<PathSegment>, String)>,
+    ) {
+        el.walk(|path, node| {
+            // Past `max_nodes` expansions `resolve_all` fails anyway.
+            if found.len() > self.max_nodes {
+                return;
+            }
+            if let YamlElement::Alias(alias) = node {
+                let at = [prefix, path].concat();
+                found.push((at.clone(), alias.clone()));
+                // Cycles and nesting too deep are left for `resolve_all`
+                // to report.
+                if let Some((name, target)) = self.anchor.get_key_value(alias) {
+                    if !expanding.contains(&name.as_str()) && at.len() <= self.max_depth {
+                        expanding.push(name);
+                        self.expansions(target, &at, expanding, found);
+                        expanding.pop();
+                    }
+                }
+            }
+        });
+    }
+
+    /// Boxes a finished node, recording it in the anchor table if it has an
+    /// anchor.
+    fn anchored(&mut self, el: YamlElement) -> BoxedYamlElement {
+        let el = Box::new(el);
+        if let YamlElement::Scalar(_, _, Some(anchor), ..)
+        | YamlElement::Map(_, _, Some(anchor))
+        | YamlElement::Set(_, _, Some(anchor)) = el.as_ref()
+        {
+            if self.anchor.insert(anchor.clone(), el.clone()).is_some() {
+                self.redefined.insert(anchor.clone());
+            }
+        }
+        el
+    }
+
+    /// Consumes every event of the next node. `depth` counts the
+    /// collections enclosing it.
+    fn node(
+        &mut self,
+        iter: &mut Events,
+        options: &YamlDocumentBuilder,
+        depth: usize,
+    ) -> Result<BoxedYamlElement, YamlError> {
+        match iter.peek()? {
+            Event::SequenceStart { .. } => self.sequence(iter, options, depth),
+            Event::MappingStart { .. } => self.map(iter, options, depth),
+            _ => match iter.next()? {
+                Event::Scalar {
+                    value,
+                    anchor,
+                    tag,
+                    style,
+                    ..
+                } => {
+                    let resolved = options.resolve(&value, tag.as_deref(), style);
+                    Ok(self.anchored(YamlElement::Scalar(value, tag, anchor, style, resolved)))
+                }
+                Event::Alias { anchor } => Ok(Box::new(YamlElement::Alias(anchor))),
+                event => Err(iter.rejected(event)),
+            },
+        }
+    }
+
+    fn sequence(
+        &mut self,
+        iter: &mut Events,
+        options: &YamlDocumentBuilder,
+        depth: usize,
+    ) -> Result<BoxedYamlElement, YamlError> {
+        let (anchor, tag) = match iter.next()? {
+            Event::SequenceStart { anchor, tag, .. } => (anchor, tag),
+            event => return Err(iter.rejected(event)),
+        };
+        if depth >= options.max_depth {
+            return Err(YamlError::DepthExceeded(Some(iter.position)));
+        }
+        let mut root = YamlSet::new();
+        while !matches!(iter.peek()?, Event::SequenceEnd) {
+            root.push(self.node(iter, options, depth + 1)?);
+        }
+        iter.next()?;
+        Ok(self.anchored(YamlElement::Set(root, tag, anchor)))
+    }
+
+    fn map(
+        &mut self,
+        iter: &mut Events,
+        options: &YamlDocumentBuilder,
+        depth: usize,
+    ) -> Result<BoxedYamlElement, YamlError> {
+        let (anchor, tag) = match iter.next()? {
+            Event::MappingStart { anchor, tag, .. } => (anchor, tag),
+            event => return Err(iter.rejected(event)),
+        };
+        if depth >= options.max_depth {
+            return Err(YamlError::DepthExceeded(Some(iter.position)));
+        }
+        let mut map = YamlMap::new();
+        // The tag, style and resolved tag of each key, by its index in `map`,
+        // for telling a repeat from a different key with the same text.
+        let mut kinds = vec![];
+        while !matches!(iter.peek()?, Event::MappingEnd) {
+            let (key, kind) = match iter.next()? {
+                Event::Scalar {
+                    value,
+                    anchor,
+                    tag,
+                    style,
+                    ..
+                } => {
+                    let resolved = options.resolve(&value, tag.as_deref(), style);
+                    if let Some(anchor) = anchor {
+                        self.anchored(YamlElement::Scalar(
+                            value.clone(),
+                            tag.clone(),
+                            Some(anchor),
+                            style,
+                            resolved.clone(),
+                        ));
+                    }
+                    (value, (tag, style, resolved))
+                }
+                // An alias key stands for the anchored scalar, which YAML
+                // requires to be defined earlier. Keys are stored as plain
+                // strings, so a key's anchor lives only in the anchor table.
+                Event::Alias { anchor } => match self.anchor.get(&anchor).map(AsRef::as_ref) {
+                    Some(YamlElement::Scalar(key, tag, _, style, resolved)) => {
+                        (key.clone(), (tag.clone(), *style, resolved.clone()))
+                    }
+                    Some(_) => return Err(YamlError::ComplexKey(iter.position)),
+                    None => return Err(YamlError::UnknownAnchor(anchor)),
+                },
+                Event::SequenceStart { .. } | Event::MappingStart { .. } => {
+                    return Err(YamlError::ComplexKey(iter.position))
+                }
+                event => return Err(iter.rejected(event)),
+            };
+            if let Some(i) = map.get_index_of(&key) {
+                if !same_key_type(&key, &kinds[i], &kind) {
+                    return Err(YamlError::KeyCollision(key, iter.position));
+                }
+                match options.duplicate_keys {
+                    DuplicateKeys::LastWins => {}
+                    DuplicateKeys::Reject => {
+                        return Err(YamlError::DuplicateKey(key, iter.position))
+                    }
+                    DuplicateKeys::Collect => self.duplicates.push((key.clone(), iter.position)),
+                }
+            }
+            let value = self.node(iter, options, depth + 1)?;
+            if map.insert(key, value).is_none() {
+                kinds.push(kind);
+            }
+        }
+        iter.next()?;
+        Ok(self.anchored(YamlElement::Map(map, tag, anchor)))
+    }
+
+    fn sort_keys(&mut self) {
+        let mut sort = |el: &mut YamlElement| {
+            if let YamlElement::Map(map, ..) = el {
+                map.sort_keys();
+            }
+            Ok(())
+        };
+        for el in self.root.iter_mut().chain(self.anchor.values_mut()) {
+            let _ = el.try_for_each_mut(&mut sort);
+        }
+    }
+
+    /// Splices `<<` merge keys into their maps once every anchor of the
+    /// document is known, so a merge may name an anchor defined after it.
+    /// Maps are rewritten in place, and only those holding a `<<` key;
+    /// merge sources are read from the anchor table as it was parsed. The
+    /// entries they copy count against `max_nodes`.
+    fn merge_keys(&mut self) -> Result<(), YamlError> {
+        let mut budget = self.max_nodes;
+        let mut root = mem::take(&mut self.root);
+        let merged = root
+            .iter_mut()
+            .try_for_each(|el| self.merge_in_place(el, &mut vec![], &mut budget, 0));
+        self.root = root;
+        merged?;
+        // As in `resolve_all`, anchored nodes of the tree are already
+        // merged; only anchors outside it are merged here.
+        let mut done = BTreeMap::new();
+        for el in &self.root {
+            el.anchored_nodes(&mut done);
+        }
+        let mut anchor = vec![];
+        for (name, target) in &self.anchor {
+            if target.find_all(|el| el.contains_key(MERGE_KEY)).is_empty() {
+                continue;
+            }
+            let target = match done.get(name.as_str()) {
+                Some(el) => (*el).clone(),
+                None => {
+                    let mut target = target.as_ref().clone();
+                    self.merge_in_place(&mut target, &mut vec![], &mut budget, 0)?;
+                    target
+                }
+            };
+            anchor.push((name.clone(), Box::new(target)));
+        }
+        self.anchor.extend(anchor);
+        Ok(())
+    }
+
+    /// Explicit keys win over merged ones, and earlier merge sources win
+    /// over later ones. A `<<` whose value isn't a map, an alias to one or a
+    /// sequence of those is kept as an ordinary key.
+    fn merge_in_place<'a>(
+        &'a self,
+        element: &mut YamlElement,
+        expanding: &mut Vec<&'a str>,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<(), YamlError> {
+        match element {
+            YamlElement::Map(map, ..) => {
+                self.descend(depth)?;
+                for v in map.values_mut() {
+                    self.merge_in_place(v, expanding, budget, depth + 1)?;
+                }
+                let sources = match map.get(MERGE_KEY) {
+                    Some(value) => self.merge_sources(value, expanding, budget, depth)?,
+                    None => None,
+                };
+                if let Some(sources) = sources {
+                    let mut inherited = sources
+                        .into_iter()
+                        .flatten()
+                        .filter(|(k, _)| !map.contains_key(k))
+                        .collect::<Vec<_>>();
+                    let mut merged = YamlMap::with_capacity(map.len() + inherited.len());
+                    for (k, v) in mem::take(map) {
+                        if k != MERGE_KEY {
+                            merged.insert(k, v);
+                            continue;
+                        }
+                        for (k, v) in inherited.drain(..) {
+                            merged.entry(k).or_insert(v);
+                        }
+                    }
+                    *map = merged;
+                }
+            }
+            YamlElement::Set(set, ..) => {
+                self.descend(depth)?;
+                for v in set {
+                    self.merge_in_place(v, expanding, budget, depth + 1)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn merge_sources<'a>(
+        &'a self,
+        value: &YamlElement,
+        expanding: &mut Vec<&'a str>,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<Option<Vec<YamlMap>>, YamlError> {
+        match value {
+            YamlElement::Set(set, ..) => set
+                .iter()
+                .map(|v| self.merge_source(v, expanding, budget, depth))
+                .collect(),
+            v => Ok(self
+                .merge_source(v, expanding, budget, depth)?
+                .map(|map| vec![map])),
+        }
+    }
+
+    fn merge_source<'a>(
+        &'a self,
+        value: &YamlElement,
+        expanding: &mut Vec<&'a str>,
+        budget: &mut usize,
+        depth: usize,
+    ) -> Result<Option<YamlMap>, YamlError> {
+        let merged = match value {
+            YamlElement::Alias(alias) => match self.anchor.get_key_value(alias) {
+                Some(_) if expanding.contains(&alias.as_str()) => {
+                    return Err(YamlError::CyclicAlias(alias.clone()))
+                }
+                Some((name, target)) => {
+                    self.spend(budget, target.node_count())?;
+                    let mut merged = target.as_ref().clone().without_anchors();
+                    expanding.push(name);
+                    self.merge_in_place(&mut merged, expanding, budget, depth)?;
+                    expanding.pop();
+                    merged
+                }
+                None => return Err(YamlError::UnknownAnchor(alias.clone())),
+            },
+            // Already merged, as values are merged before their map.
+            v @ YamlElement::Map(..) => {
+                self.spend(budget, v.node_count())?;
+                v.clone().without_anchors()
+            }
+            _ => return Ok(None),
+        };
+        match merged {
+            YamlElement::Map(map, ..) => Ok(Some(map)),
+            _ => Ok(None),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a YamlDocument {
+    type Item = &'a YamlElement;
+    type IntoIter =
+        iter::Map<slice::Iter<'a, BoxedYamlElement>, fn(&BoxedYamlElement) -> &YamlElement>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.root.iter().map(AsRef::as_ref)
+    }
+}
+
+/// Parses a single document, as `YamlDocument::from_reader` does.
+impl FromStr for YamlDocument {
+    type Err = YamlError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_reader(s.as_bytes())
+    }
+}
+
+const MERGE_KEY: &str = "<<";
+/// The default `YamlDocumentBuilder::max_depth`.
+pub(crate) const MAX_DEPTH: usize = 128;
+/// The default `YamlDocumentBuilder::max_nodes`.
+pub(crate) const MAX_NODES: usize = 1_000_000;
+const YAML_TAG_PREFIX: &str = "tag:yaml.org,2002:";
+
+/// A 1-based location in the source text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl From<&sys::yaml_mark_t> for Position {
+    fn from(mark: &sys::yaml_mark_t) -> Self {
+        Self {
+            line: mark.line + 1,
+            column: mark.column + 1,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} column {}", self.line, self.column)
+    }
+}
+
+/// Keeps the reader's I/O error, which libyaml only hands back through
+/// `Parser::parse`.
+struct Source<R> {
+    reader: R,
+    error: Rc<Cell<Option<io::Error>>>,
+}
+
+impl<R: io::Read> io::Read for Source<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.reader.read(buf).map_err(|e| {
+            let kind = e.kind();
+            self.error.set(Some(e));
+            kind.into()
+        })
+    }
+}
+
+/// Drives the libyaml parser directly instead of through `ParserIter` so
+/// the start mark of every event is available for error messages.
+struct Events<'a> {
+    parser: Box<Parser<'a>>,
+    reader_error: Rc<Cell<Option<io::Error>>>,
+    peeked: Option<(Event, Position)>,
+    position: Position,
+}
+
+impl<'a> Events<'a> {
+    fn new(reader: impl io::Read + 'a, encoding: Option<Encoding>) -> Result<Self, YamlError> {
+        let reader_error = Rc::new(Cell::new(None));
+        let mut parser = ParserBuilder::new(Source {
+            reader,
+            error: reader_error.clone(),
+        })?;
+        if let Some(encoding) = encoding {
+            parser = parser.encoding(encoding);
+        }
+        let parser = parser.finish();
+        Ok(Self {
+            parser,
+            reader_error,
+            peeked: None,
+            position: Position { line: 1, column: 1 },
+        })
+    }
+
+    fn peek(&mut self) -> Result<&Event, YamlError> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.parse()?);
+        }
+        Ok(&self.peeked.as_ref().unwrap().0)
+    }
+
+    fn next(&mut self) -> Result<Event, YamlError> {
+        let (event, position) = match self.peeked.take() {
+            Some(peeked) => peeked,
+            None => self.parse()?,
+        };
+        self.position = position;
+        Ok(event)
+    }
+
+    /// Reports the last consumed `event` as out of place.
+    fn rejected(&self, event: Event) -> YamlError {
+        YamlError::UnexpectedEvent(event, self.position)
+    }
+
+    fn parse(&mut self) -> Result<(Event, Position), YamlError> {
+        let parser = self.parser.as_raw_ptr();
+        let mut raw = MaybeUninit::<sys::yaml_event_t>::zeroed();
+        unsafe {
+            if sys::yaml_parser_parse(parser, raw.as_mut_ptr()) == 0 {
+                return Err(match self.reader_error.take() {
+                    Some(e) => YamlError::Io(e),
+                    None => syntax_error(&*parser),
+                });
+            }
+            let raw = raw.assume_init();
+            if raw.type_ == sys::YAML_NO_EVENT {
+                return Err(YamlError::UnexpectedEnd);
+            }
+            let position = (&raw.start_mark).into();
+            Ok((Event::from_raw(raw).map_err(ParserError::from)?, position))
+        }
+    }
+}
+
+fn write_document(
+    writer: impl io::Write,
+    root: &YamlElement,
+    version: Option<VersionDirective>,
+    tags: Vec<TagDirective>,
+    implicit_start: bool,
+    implicit_end: bool,
+) -> Result<(), EmitterError> {
+    // Without `unicode`, libyaml escapes every non-ASCII character.
+    let mut emitter = EmitterBuilder::new(writer)?.unicode(true).finish();
+    emitter.emit(Event::StreamStart { encoding: None })?;
+    emitter.emit(Event::DocumentStart {
+        version,
+        tags,
+        implicit: implicit_start,
+    })?;
+    root.emit(&mut emitter)?;
+    emitter.emit(Event::DocumentEnd {
+        implicit: implicit_end,
+    })?;
+    emitter.emit(Event::StreamEnd)?;
+    emitter.flush()
+}
+
+fn into_io_error(e: EmitterError) -> io::Error {
+    match e {
+        EmitterError::IoError(e) => e,
+        e => io::Error::other(e),
+    }
+}
+
+fn syntax_error(parser: &sys::yaml_parser_t) -> YamlError {
+    let text = |ptr: *const c_char| {
+        if ptr.is_null() {
+            None
+        } else {
+            Some(
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned(),
+            )
+        }
+    };
+    match text(parser.problem) {
+        // The reader decodes ahead of the scanner, so only the byte offset
+        // of an encoding error is known.
+        Some(problem) if parser.error == sys::YAML_READER_ERROR => YamlError::InvalidEncoding {
+            problem,
+            offset: parser.problem_offset,
+        },
+        Some(problem) => YamlError::Syntax {
+            problem: match text(parser.context) {
+                Some(context) => format!("{} {}", problem, context),
+                None => problem,
+            },
+            position: (&parser.problem_mark).into(),
+        },
+        None => YamlError::Parse(ParserError::LibYamlError),
+    }
+}
+
+/// `leading_zero_octal` reads `012` as YAML 1.1 does, rather than as the
+/// decimal it is in YAML 1.2.
+/// The tag, style and resolved tag of a map key.
+type KeyKind = (Option<String>, Option<ScalarStyle>, Option<String>);
+
+/// Whether `key` has the same type spelled with `a` as with `b`.
+fn same_key_type(key: &str, a: &KeyKind, b: &KeyKind) -> bool {
+    let scalar = |(tag, style, resolved): &KeyKind| {
+        YamlElement::Scalar(key.to_string(), tag.clone(), None, *style, resolved.clone())
+    };
+    let (a, b) = (scalar(a), scalar(b));
+    let typed = |el| YamlElement::typed_scalar(el).map(|typed| mem::discriminant(&typed));
+    typed(&a) == typed(&b)
+}
+
+fn tag_matches(tag: Option<&str>, wanted: &str) -> bool {
+    match (tag, wanted.strip_prefix("!!")) {
+        (Some(t), Some(short)) => t.strip_prefix(YAML_TAG_PREFIX) == Some(short),
+        (Some(t), None) => t == wanted,
+        (None, _) => false,
+    }
+}
+
+fn parse_int(s: &str, leading_zero_octal: bool) -> Option<i128> {
+    let s = without_separators(s);
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s.strip_prefix('+').unwrap_or(&s)),
+    };
+    let (radix, digits) = if let Some(digits) = digits.strip_prefix("0x") {
+        (16, digits)
+    } else if let Some(digits) = digits.strip_prefix("0o") {
+        (8, digits)
+    } else if let Some(digits) = digits.strip_prefix("0b") {
+        (2, digits)
+    } else if leading_zero_octal && digits.len() > 1 && digits.starts_with('0') {
+        (8, &digits[1..])
+    } else {
+        (10, digits)
+    };
+    // `from_str_radix` would accept a second sign.
+    if digits.starts_with(['+', '-']) {
+        return None;
+    }
+    let n = i128::from_str_radix(digits, radix).ok()?;
+    Some(if negative { -n } else { n })
+}
+
+/// Drops `_` digit separators, borrowing when there are none.
+fn without_separators(s: &str) -> Cow<'_, str> {
+    if s.contains('_') {
+        Cow::Owned(s.replace('_', ""))
+    } else {
+        Cow::Borrowed(s)
+    }
+}
+
+/// A map key (`str`, `String` or a reference to either) or a sequence
+/// position (`usize`) for indexing into a `YamlElement`.
+pub trait YamlIndex: private::Sealed {
+    #[doc(hidden)]
+    fn index_into<'v>(&self, el: &'v YamlElement) -> Option<&'v YamlElement>;
+
+    #[doc(hidden)]
+    fn index_or_insert<'v>(&self, el: &'v mut YamlElement) -> &'v mut YamlElement;
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T: ?Sized + Sealed> Sealed for &T {}
+}
+
+impl YamlIndex for usize {
+    fn index_into<'v>(&self, el: &'v YamlElement) -> Option<&'v YamlElement> {
+        match el {
+            YamlElement::Set(set, ..) => set.get(*self).map(AsRef::as_ref),
+            _ => None,
+        }
+    }
+
+    fn index_or_insert<'v>(&self, el: &'v mut YamlElement) -> &'v mut YamlElement {
+        match el {
+            YamlElement::Set(set, ..) => {
+                let len = set.len();
+                match set.get_mut(*self) {
+                    Some(item) => item,
+                    None => panic!("index {} out of range for sequence of length {}", self, len),
+                }
+            }
+            el => panic!("cannot index into {} with `{}`", el.describe(), self),
+        }
+    }
+}
+
+impl YamlIndex for str {
+    fn index_into<'v>(&self, el: &'v YamlElement) -> Option<&'v YamlElement> {
+        el.get(self)
+    }
+
+    fn index_or_insert<'v>(&self, el: &'v mut YamlElement) -> &'v mut YamlElement {
+        if let YamlElement::None = el {
+            *el = YamlElement::Map(YamlMap::new(), None, None);
+        }
+        match el {
+            YamlElement::Map(map, ..) => map
+                .entry(self.to_string())
+                .or_insert_with(|| Box::new(YamlElement::None)),
+            el => panic!("cannot index into {} with `{}`", el.describe(), self),
+        }
+    }
+}
+
+impl YamlIndex for String {
+    fn index_into<'v>(&self, el: &'v YamlElement) -> Option<&'v YamlElement> {
+        self.as_str().index_into(el)
+    }
+
+    fn index_or_insert<'v>(&self, el: &'v mut YamlElement) -> &'v mut YamlElement {
+        self.as_str().index_or_insert(el)
+    }
+}
+
+impl<T: ?Sized + YamlIndex> YamlIndex for &T {
+    fn index_into<'v>(&self, el: &'v YamlElement) -> Option<&'v YamlElement> {
+        (**self).index_into(el)
+    }
+
+    fn index_or_insert<'v>(&self, el: &'v mut YamlElement) -> &'v mut YamlElement {
+        (**self).index_or_insert(el)
+    }
+}
+
+/// A missing key or position, or indexing a node of the wrong kind, gives
+/// `YamlElement::None`.
+impl<I: YamlIndex> Index<I> for YamlElement {
+    type Output = YamlElement;
+
+    fn index(&self, index: I) -> &Self::Output {
+        index.index_into(self).unwrap_or(&Self::None)
+    }
+}
+
+/// Assigning through a missing key inserts it, and a `None` element
+/// becomes a map first. An out-of-range position, or indexing any other
+/// node, panics.
+impl<I: YamlIndex> IndexMut<I> for YamlElement {
+    fn index_mut(&mut self, index: I) -> &mut Self::Output {
+        index.index_or_insert(self)
+    }
 }
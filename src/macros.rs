@@ -0,0 +1,21 @@
+/// Indexes into an element and converts the node found, as in
+/// `yaml_get!(doc[0], "chip" => "ports" => 0 => "id" : i64)`. String
+/// segments are map keys and integers index sequences; a segment that is
+/// anything more than a literal or a name needs parentheses. `bool`, `i64`,
+/// `u64`, `f64` and `str` use the matching typed accessor and other types
+/// parse with `as_scalar`, so the result is always an `Option`.
+#[macro_export]
+macro_rules! yaml_get {
+    ($el:expr, $($segment:tt)=>+ : $ty:ident) => {
+        $crate::yaml_get!(@as (&$el)$([$crate::yaml_get!(@segment $segment)])+, $ty)
+    };
+    // Unwrapped, so `(key)` doesn't trip `unused_parens` at the caller.
+    (@segment ($segment:expr)) => { $segment };
+    (@segment $segment:tt) => { $segment };
+    (@as $node:expr, bool) => { $node.as_bool() };
+    (@as $node:expr, i64) => { $node.as_i64() };
+    (@as $node:expr, u64) => { $node.as_u64() };
+    (@as $node:expr, f64) => { $node.as_f64() };
+    (@as $node:expr, str) => { $node.as_str() };
+    (@as $node:expr, $ty:ident) => { $node.as_scalar::<$ty>() };
+}
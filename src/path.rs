@@ -0,0 +1,95 @@
+use crate::YamlElement;
+use crate::YamlError;
+
+/// Walks into an element one segment at a time and remembers the path, so
+/// a failed lookup says which segment was missing or mistyped, as in
+/// `MissingPath("chip/ports/3")`.
+#[derive(Debug, Clone)]
+pub struct TypedPath<'a> {
+    node: Option<&'a YamlElement>,
+    path: String,
+}
+
+impl<'a> TypedPath<'a> {
+    pub fn new(root: &'a YamlElement) -> Self {
+        Self {
+            node: Some(root),
+            path: String::new(),
+        }
+    }
+
+    pub fn key(self, key: &str) -> Self {
+        self.step(key, |el| el.get(key))
+    }
+
+    pub fn index(self, index: usize) -> Self {
+        self.step(&index.to_string(), |el| {
+            el.as_vec_ref()?.get(index).map(AsRef::as_ref)
+        })
+    }
+
+    /// Follows a slash-delimited path the way `YamlElement::pointer` does.
+    pub fn pointer(self, path: &str) -> Self {
+        path.split('/')
+            .filter(|segment| !segment.is_empty())
+            .fold(self, |walk, segment| {
+                walk.step(segment, |el| el.pointer(segment))
+            })
+    }
+
+    /// Once a segment is missing the path stops growing, so it ends at the
+    /// first one that failed.
+    fn step(
+        mut self,
+        segment: &str,
+        next: impl FnOnce(&'a YamlElement) -> Option<&'a YamlElement>,
+    ) -> Self {
+        if let Some(node) = self.node {
+            if !self.path.is_empty() {
+                self.path.push('/');
+            }
+            self.path.push_str(segment);
+            self.node = next(node);
+        }
+        self
+    }
+
+    pub fn get(self) -> Result<&'a YamlElement, YamlError> {
+        match self.node {
+            Some(node) => Ok(node),
+            None => Err(YamlError::MissingPath(self.path)),
+        }
+    }
+
+    pub fn as_str(self) -> Result<&'a str, YamlError> {
+        self.convert("string", YamlElement::as_str)
+    }
+
+    pub fn as_bool(self) -> Result<bool, YamlError> {
+        self.convert("boolean", YamlElement::as_bool)
+    }
+
+    pub fn as_i64(self) -> Result<i64, YamlError> {
+        self.convert("integer", YamlElement::as_i64)
+    }
+
+    pub fn as_u64(self) -> Result<u64, YamlError> {
+        self.convert("unsigned integer", YamlElement::as_u64)
+    }
+
+    pub fn as_f64(self) -> Result<f64, YamlError> {
+        self.convert("float", YamlElement::as_f64)
+    }
+
+    fn convert<T>(
+        self,
+        expected: &'static str,
+        f: impl FnOnce(&'a YamlElement) -> Option<T>,
+    ) -> Result<T, YamlError> {
+        let node = match self.node {
+            Some(node) => node,
+            None => return Err(YamlError::MissingPath(self.path)),
+        };
+        f(node).ok_or_else(|| YamlError::AtPath(self.path, Box::new(node.mismatch(expected))))
+    }
+}
@@ -0,0 +1,70 @@
+use std::fmt;
+
+use crate::YAML_TAG_PREFIX;
+
+/// Types untagged plain scalars at load time by giving them a resolved
+/// tag, which the typed accessors then honor. The text is kept as written.
+pub trait ScalarResolver: Send + Sync {
+    /// The full tag for `value`, or `None` to leave it untagged.
+    fn resolve(&self, value: &str) -> Option<String>;
+}
+
+impl fmt::Debug for dyn ScalarResolver {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ScalarResolver")
+    }
+}
+
+/// The YAML 1.2 core schema: `null`, `bool`, `int` and `float` by their
+/// core spellings, and `str` for everything else. YAML 1.1 forms such as
+/// `yes` or `0b101` become strings.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CoreSchema;
+
+impl ScalarResolver for CoreSchema {
+    fn resolve(&self, value: &str) -> Option<String> {
+        let kind = match value {
+            "" | "~" | "null" | "Null" | "NULL" => "null",
+            "true" | "True" | "TRUE" | "false" | "False" | "FALSE" => "bool",
+            _ if is_int(value) => "int",
+            _ if is_float(value) => "float",
+            _ => "str",
+        };
+        Some(format!("{}{}", YAML_TAG_PREFIX, kind))
+    }
+}
+
+fn is_int(s: &str) -> bool {
+    let digits = |s: &str, radix| !s.is_empty() && s.chars().all(|c| c.is_digit(radix));
+    if let Some(hex) = s.strip_prefix("0x") {
+        digits(hex, 16)
+    } else if let Some(octal) = s.strip_prefix("0o") {
+        digits(octal, 8)
+    } else {
+        digits(s.strip_prefix(['-', '+']).unwrap_or(s), 10)
+    }
+}
+
+fn is_float(s: &str) -> bool {
+    let unsigned = s.strip_prefix(['-', '+']).unwrap_or(s);
+    if matches!(unsigned, ".inf" | ".Inf" | ".INF") || matches!(s, ".nan" | ".NaN" | ".NAN") {
+        return true;
+    }
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(i) => (&unsigned[..i], Some(&unsigned[i + 1..])),
+        None => (unsigned, None),
+    };
+    let (whole, fraction) = match mantissa.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (mantissa, None),
+    };
+    let all_digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let mantissa_ok = all_digits(whole)
+        && fraction.is_none_or(all_digits)
+        && (!whole.is_empty() || fraction.is_some_and(|f| !f.is_empty()));
+    let exponent_ok = exponent.is_none_or(|e| {
+        let e = e.strip_prefix(['-', '+']).unwrap_or(e);
+        !e.is_empty() && all_digits(e)
+    });
+    mantissa_ok && exponent_ok
+}
@@ -0,0 +1,164 @@
+use std::error::Error;
+use std::fmt;
+
+use crate::YamlElement;
+
+/// The shape a node is expected to have.
+#[derive(Debug, Clone)]
+pub enum Kind {
+    Any,
+    Str,
+    Int,
+    Float,
+    Bool,
+    Seq(Box<Kind>),
+    Map(Schema),
+}
+
+impl Kind {
+    pub fn seq(items: Kind) -> Self {
+        Kind::Seq(Box::new(items))
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Kind::Any => "any node",
+            Kind::Str => "string",
+            Kind::Int => "integer",
+            Kind::Float => "float",
+            Kind::Bool => "boolean",
+            Kind::Seq(_) => "sequence",
+            Kind::Map(_) => "map",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Field {
+    key: String,
+    kind: Kind,
+    required: bool,
+}
+
+/// Expected keys of a map and the kind of their values. Keys the schema
+/// doesn't mention are allowed.
+#[derive(Debug, Clone, Default)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+impl Schema {
+    pub fn map() -> Self {
+        Self::default()
+    }
+
+    pub fn required(self, key: impl Into<String>, kind: Kind) -> Self {
+        self.field(key.into(), kind, true)
+    }
+
+    pub fn optional(self, key: impl Into<String>, kind: Kind) -> Self {
+        self.field(key.into(), kind, false)
+    }
+
+    fn field(mut self, key: String, kind: Kind, required: bool) -> Self {
+        self.fields.push(Field {
+            key,
+            kind,
+            required,
+        });
+        self
+    }
+
+    /// Checks the whole element and reports every mismatch rather than
+    /// stopping at the first one.
+    pub fn validate(&self, el: &YamlElement) -> Result<(), Vec<ValidationError>> {
+        let mut errors = vec![];
+        self.check(el, "", &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(&self, el: &YamlElement, path: &str, errors: &mut Vec<ValidationError>) {
+        if el.as_map().is_none() {
+            errors.push(ValidationError::new(path, "map", el));
+            return;
+        }
+        for field in &self.fields {
+            let path = join(path, &field.key);
+            match el.get(&field.key) {
+                Some(value) => check(&field.kind, value, &path, errors),
+                None if field.required => errors.push(ValidationError {
+                    path,
+                    expected: field.kind.name(),
+                    found: "nothing".to_string(),
+                }),
+                None => {}
+            }
+        }
+    }
+}
+
+fn check(kind: &Kind, el: &YamlElement, path: &str, errors: &mut Vec<ValidationError>) {
+    let matches = match kind {
+        Kind::Any => true,
+        Kind::Str => el.as_str().is_some(),
+        Kind::Int => el.as_i64().is_some() || el.as_u64().is_some(),
+        Kind::Float => el.as_f64().is_some(),
+        Kind::Bool => el.as_bool().is_some(),
+        Kind::Seq(items) => match el {
+            YamlElement::Set(set, ..) => {
+                for (i, item) in set.iter().enumerate() {
+                    check(items, item, &join(path, &i.to_string()), errors);
+                }
+                true
+            }
+            _ => false,
+        },
+        Kind::Map(schema) => return schema.check(el, path, errors),
+    };
+    if !matches {
+        errors.push(ValidationError::new(path, kind.name(), el));
+    }
+}
+
+fn join(path: &str, segment: &str) -> String {
+    if path.is_empty() {
+        segment.to_string()
+    } else {
+        format!("{}/{}", path, segment)
+    }
+}
+
+/// A node that doesn't fit the schema. `path` uses the slash-delimited form
+/// accepted by `YamlElement::pointer`, empty for the root.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub path: String,
+    pub expected: &'static str,
+    pub found: String,
+}
+
+impl ValidationError {
+    fn new(path: &str, expected: &'static str, el: &YamlElement) -> Self {
+        Self {
+            path: path.to_string(),
+            expected,
+            found: el.describe(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "/{}: expected {}, found {}",
+            self.path, self.expected, self.found
+        )
+    }
+}
+
+impl Error for ValidationError {}
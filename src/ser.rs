@@ -0,0 +1,56 @@
+use serde::ser;
+use serde::ser::SerializeMap;
+use serde::ser::SerializeSeq;
+use serde::Serialize;
+use serde::Serializer;
+
+use crate::Typed;
+use crate::YamlDocument;
+use crate::YamlElement;
+
+/// Scalars are typed as `to_json` does, and tags, anchors and styles are
+/// dropped. Aliases fail with a custom error, as only their document
+/// knows the anchors; serialize the `YamlDocument` to have them expanded.
+impl Serialize for YamlElement {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.typed_scalar() {
+            Some(Typed::Null) => serializer.serialize_unit(),
+            Some(Typed::Bool(b)) => serializer.serialize_bool(b),
+            Some(Typed::Int(n)) => serializer.serialize_i64(n),
+            Some(Typed::UInt(n)) => serializer.serialize_u64(n),
+            Some(Typed::Float(n)) => serializer.serialize_f64(n),
+            Some(Typed::Str(s)) => serializer.serialize_str(s),
+            None => match self {
+                YamlElement::Map(map, ..) => {
+                    let mut out = serializer.serialize_map(Some(map.len()))?;
+                    for (k, v) in map {
+                        out.serialize_entry(k, v)?;
+                    }
+                    out.end()
+                }
+                YamlElement::Set(set, ..) => {
+                    let mut out = serializer.serialize_seq(Some(set.len()))?;
+                    for v in set {
+                        out.serialize_element(v)?;
+                    }
+                    out.end()
+                }
+                _ => Err(ser::Error::custom(self.mismatch("resolved element"))),
+            },
+        }
+    }
+}
+
+/// Serializes the root with every alias expanded, or a unit for an empty
+/// document.
+impl Serialize for YamlDocument {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.root.first() {
+            Some(root) => self
+                .resolve(root)
+                .map_err(ser::Error::custom)?
+                .serialize(serializer),
+            None => serializer.serialize_unit(),
+        }
+    }
+}
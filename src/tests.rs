@@ -0,0 +1,660 @@
+use crate::yaml_get;
+use crate::Change;
+use crate::CoreSchema;
+use crate::DuplicateKeys;
+use crate::Encoding;
+use crate::Kind;
+use crate::PathSegment;
+use crate::ScalarResolver;
+use crate::Schema;
+use crate::TypedPath;
+use crate::ValidationError;
+use crate::YamlDocument;
+use crate::YamlDocumentBuilder;
+use crate::YamlElement;
+use crate::YamlError;
+
+fn load(s: &str) -> YamlDocument {
+    s.parse().unwrap()
+}
+
+#[test]
+fn merge_key_before_its_anchor() {
+    let doc =
+        load("device:\n  <<: *base\n  name: lamp\nbase: &base\n  name: default\n  power: 1\n");
+    let device = &doc[0]["device"];
+    assert_eq!(device["name"], "lamp");
+    assert_eq!(device["power"], 1);
+    assert!(!device.contains_key("<<"));
+}
+
+#[test]
+fn expanded_copies_drop_nested_anchors() {
+    let mut doc = load("b: &o\n  inner: &i 1\nc: *o\n");
+    doc.resolve_all().unwrap();
+    assert_eq!(doc[0]["c"]["inner"].get_anchor(), None);
+    assert_eq!(doc[0]["b"]["inner"].get_anchor(), Some("i"));
+    assert_eq!(doc[0].to_string().matches("&i").count(), 1);
+}
+
+#[test]
+fn merged_copies_drop_nested_anchors() {
+    let doc = load("b: &o\n  inner: &i 1\nc:\n  <<: *o\n");
+    assert_eq!(doc[0]["c"]["inner"].get_anchor(), None);
+    assert_eq!(doc[0].to_string().matches("&i").count(), 1);
+}
+
+/// Each level holds two copies of the one before, so level `n` expands to
+/// about `2^n` nodes.
+fn laughs(n: usize, level: impl Fn(usize) -> String) -> String {
+    let mut s = "l0: &l0 [lol]\n".to_string();
+    for i in 1..=n {
+        s.push_str(&format!("l{}: &l{} {}\n", i, i, level(i - 1)));
+    }
+    s
+}
+
+#[test]
+fn alias_bomb_hits_the_node_limit() {
+    let s = laughs(40, |prev| format!("[*l{}, *l{}]", prev, prev));
+    let options = YamlDocumentBuilder::new().max_nodes(10_000);
+    let mut doc = options.build_from_str(&s).unwrap();
+    assert!(matches!(
+        doc.resolve_all(),
+        Err(YamlError::ExpansionLimit(10_000))
+    ));
+    let checked = options.resolve_aliases(true).build_from_str(&s);
+    assert!(matches!(checked, Err(YamlError::ExpansionLimit(_))));
+}
+
+#[test]
+fn merge_key_bomb_hits_the_node_limit() {
+    let s = laughs(40, |prev| {
+        format!("{{a: {{<<: *l{}}}, b: {{<<: *l{}}}}}", prev, prev)
+    });
+    let options = YamlDocumentBuilder::new().max_nodes(10_000);
+    assert!(matches!(
+        options.build_from_str(&s),
+        Err(YamlError::ExpansionLimit(_))
+    ));
+}
+
+#[test]
+fn only_expanded_nodes_count_against_the_limit() {
+    let s = (0..100)
+        .map(|i| format!("k{}: [{}, {}]\n", i, i, i))
+        .collect::<String>();
+    let doc = YamlDocumentBuilder::new()
+        .max_nodes(10)
+        .resolve_aliases(true)
+        .build_from_str(&(s + "a: &a [1, 2]\nb: *a\n"))
+        .unwrap();
+    assert_eq!(doc[0]["b"].len(), 2);
+}
+
+#[test]
+fn alias_resolution_keeps_the_tag() {
+    let doc = load("base: &base !device {id: 1}\ncopy: *base\n");
+    let target = doc.resolve_alias(&doc[0]["copy"]).unwrap();
+    assert_eq!(target.get_tag(), doc[0]["base"].get_tag());
+    assert_eq!(target.get_tag(), Some("!device"));
+    let resolved = doc.resolve(&doc[0]["copy"]).unwrap();
+    assert_eq!(resolved.get_tag(), Some("!device"));
+}
+
+/// Anchor `n` nests `depth` sequences around an alias to anchor `n - 1`.
+fn deep_chain(anchors: usize, depth: usize) -> String {
+    let mut s = "a0: &a0 x\n".to_string();
+    for i in 1..anchors {
+        let open = "[".repeat(depth);
+        let close = "]".repeat(depth);
+        s.push_str(&format!("a{}: &a{} {}*a{}{}\n", i, i, open, i - 1, close));
+    }
+    s
+}
+
+#[test]
+fn alias_chains_hit_the_depth_limit() {
+    let s = deep_chain(100, 100);
+    let options = YamlDocumentBuilder::new().max_depth(200);
+    let mut doc = options.build_from_str(&s).unwrap();
+    let last = doc[0]["a99"].clone();
+    assert!(matches!(
+        doc.resolve(&last),
+        Err(YamlError::DepthExceeded(None))
+    ));
+    let mut annotated = options.build_from_str(&s).unwrap();
+    assert!(annotated.resolve_all_annotated().is_err());
+    assert!(matches!(
+        doc.resolve_all(),
+        Err(YamlError::DepthExceeded(None))
+    ));
+    let checked = options.resolve_aliases(true).build_from_str(&s);
+    assert!(matches!(checked, Err(YamlError::DepthExceeded(None))));
+}
+
+#[test]
+fn merge_chains_hit_the_depth_limit() {
+    let mut s = "m0: &m0 {x: 1}\n".to_string();
+    for i in 1..100 {
+        let open = "{a: ".repeat(100);
+        let close = "}".repeat(100);
+        s.push_str(&format!(
+            "m{}: &m{} {}{{<<: *m{}}}{}\n",
+            i,
+            i,
+            open,
+            i - 1,
+            close
+        ));
+    }
+    let loaded = YamlDocumentBuilder::new().max_depth(200).build_from_str(&s);
+    assert!(matches!(loaded, Err(YamlError::DepthExceeded(None))));
+}
+
+#[test]
+fn annotated_alias_bomb_hits_the_node_limit() {
+    let s = laughs(40, |prev| format!("[*l{}, *l{}]", prev, prev));
+    let mut doc = YamlDocumentBuilder::new()
+        .max_nodes(10_000)
+        .build_from_str(&s)
+        .unwrap();
+    assert!(matches!(
+        doc.resolve_all_annotated(),
+        Err(YamlError::ExpansionLimit(_))
+    ));
+}
+
+#[test]
+fn nesting_past_the_depth_limit_is_rejected() {
+    let s = "[".repeat(100_000);
+    assert!(matches!(
+        s.parse::<YamlDocument>(),
+        Err(YamlError::DepthExceeded(Some(_)))
+    ));
+    let s = format!("{}1{}", "{a: ".repeat(129), "}".repeat(129));
+    assert!(matches!(
+        s.parse::<YamlDocument>(),
+        Err(YamlError::DepthExceeded(Some(_)))
+    ));
+}
+
+#[test]
+fn nesting_up_to_the_depth_limit_loads() {
+    let s = format!("a: &a {}1{}\nb: *a\n", "[".repeat(126), "]".repeat(126));
+    let mut doc = load(&s);
+    doc.resolve_all().unwrap();
+    assert!(doc[0].to_string().starts_with("a: &a"));
+}
+
+#[test]
+fn display_leaves_out_names_libyaml_rejects() {
+    let el = YamlElement::Scalar("x".into(), None, Some("ä b".into()), None, None);
+    assert!(el.to_yaml_string().is_err());
+    assert_eq!(el.to_string(), "x\n");
+    let el: YamlElement = vec![
+        YamlElement::Alias("not ok".into()),
+        YamlElement::Scalar("y".into(), Some(String::new()), None, None, None),
+    ]
+    .into_iter()
+    .collect();
+    assert!(el.to_yaml_string().is_err());
+    assert_eq!(el.to_string(), "-\n- y\n");
+    let el = YamlElement::Alias("ok_1".into());
+    assert_eq!(el.to_yaml_string().unwrap(), el.to_string());
+}
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[test]
+fn deserialize_any_keeps_quoted_scalars_as_strings() {
+    let doc = load("quoted: '12'\nblock: |\n  true\nplain: 12\n");
+    let value: serde_json::Value = crate::from_element(&doc[0]).unwrap();
+    assert_eq!(value["quoted"], "12");
+    assert_eq!(value["block"], "true\n");
+    assert_eq!(value["plain"], 12);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn document_to_json_expands_aliases() {
+    let doc = load("a: &a {x: 1}\nb: *a\n");
+    assert!(doc[0].to_json().is_err());
+    let json = doc.to_json().unwrap();
+    assert_eq!(json["b"]["x"], 1);
+    assert_eq!(load("").to_json().unwrap(), serde_json::Value::Null);
+}
+
+#[cfg(all(feature = "serde", feature = "json"))]
+#[test]
+fn serializing_a_document_expands_aliases() {
+    let doc = load("a: &a [1, two]\nb: *a\n");
+    assert!(serde_json::to_value(&doc[0]).is_err());
+    let json = serde_json::to_value(&doc).unwrap();
+    assert_eq!(json["b"], serde_json::json!([1, "two"]));
+}
+
+#[test]
+fn core_schema_leading_zeros_are_decimal() {
+    let doc = YamlDocumentBuilder::new()
+        .scalar_resolver(CoreSchema)
+        .build_from_str("a: 012\nb: -012\nc: 0o12\nd: 012.5\ne: !!int 012\n")
+        .unwrap();
+    assert_eq!(doc[0]["a"].resolved_tag(), Some("tag:yaml.org,2002:int"));
+    assert!(!doc[0]["a"].is_tagged_as("!!int"));
+    assert_eq!(doc[0]["e"].as_i64(), Some(10));
+    assert_eq!(doc[0]["a"].as_i64(), Some(12));
+    assert_eq!(doc[0]["b"].as_i64(), Some(-12));
+    assert_eq!(doc[0]["c"].as_i64(), Some(10));
+    assert_eq!(doc[0]["a"].as_f64(), Some(12.0));
+    assert_eq!(doc[0]["d"].as_f64(), Some(12.5));
+    assert_eq!(load("a: 012\n")[0]["a"].as_i64(), Some(10));
+}
+
+#[test]
+fn resolved_tags_are_not_written_out() {
+    let doc = YamlDocumentBuilder::new()
+        .scalar_resolver(CoreSchema)
+        .build_from_str("a: yes\nb: !!str 1\n")
+        .unwrap();
+    assert_eq!(doc[0]["a"].as_bool(), None);
+    assert_eq!(doc[0].to_string(), "a: yes\nb: !!str 1\n");
+}
+
+#[test]
+fn aliases_bind_to_the_anchor_defined_before_them() {
+    let mut doc = load("a: &x 1\nb: *x\nc: &x 2\nd: *x\ne: &x [*x]\nf: *x\n");
+    assert_eq!(doc.anchors()["x"][0].as_i64(), Some(2));
+    doc.resolve_all().unwrap();
+    assert_eq!(doc[0]["b"].as_i64(), Some(1));
+    assert_eq!(doc[0]["d"].as_i64(), Some(2));
+    assert_eq!(doc[0]["f"][0].as_i64(), Some(2));
+}
+
+#[test]
+fn anchors_are_scoped_to_their_document() {
+    let s = "a: &x one\nb: *x\n---\na: &x two\nb: *x\n";
+    assert!(matches!(
+        s.parse::<YamlDocument>(),
+        Err(YamlError::MultipleDocuments)
+    ));
+    let mut docs = YamlDocument::from_reader_multi(s.as_bytes()).unwrap();
+    assert_eq!(docs.len(), 2);
+    for (doc, value) in docs.iter_mut().zip(["one", "two"]) {
+        doc.resolve_all().unwrap();
+        assert_eq!(doc[0]["b"], value);
+    }
+    let leaked = YamlDocument::from_reader_multi("a: &x one\n---\nb: *x\n".as_bytes()).unwrap();
+    assert_eq!(leaked[1].dangling_aliases(), vec!["x".to_string()]);
+}
+
+#[test]
+fn take_leaves_none_behind() {
+    let mut doc = load("a: {x: 1}\nb: 2\n");
+    let a = doc[0].take("a").unwrap();
+    assert_eq!(a["x"], 1);
+    assert_eq!(doc[0]["a"], YamlElement::None);
+    assert_eq!(
+        doc[0].iter_map().map(|(k, _)| k).collect::<Vec<_>>(),
+        ["a", "b"]
+    );
+    assert_eq!(doc[0].take("missing"), None);
+}
+
+#[cfg(feature = "derive")]
+#[test]
+fn derive_from_yaml() {
+    use crate::FromYaml;
+
+    #[derive(Debug, PartialEq, FromYaml)]
+    struct Device {
+        name: String,
+        #[yaml(rename = "type")]
+        kind: String,
+        power: Option<i64>,
+        ports: Vec<i64>,
+    }
+
+    let doc = load("name: lamp\ntype: light\nports: [1, 2]\n");
+    let device = Device::from_yaml(&doc[0]).unwrap();
+    assert_eq!(
+        device,
+        Device {
+            name: "lamp".into(),
+            kind: "light".into(),
+            power: None,
+            ports: vec![1, 2],
+        }
+    );
+    assert!(matches!(
+        Device::from_yaml(&load("[1]")[0]),
+        Err(YamlError::TypeMismatch {
+            expected: "map",
+            ..
+        })
+    ));
+    let err = Device::from_yaml(&load("name: lamp\ntype: light\nports: one\n")[0]).unwrap_err();
+    assert!(matches!(&err, YamlError::AtPath(key, _) if key == "ports"));
+    assert!(err.to_string().contains("ports"));
+}
+
+#[test]
+fn self_referential_anchor_is_a_cycle() {
+    let mut doc = load("a: &x [*x]\n");
+    assert!(matches!(doc.resolve_all(), Err(YamlError::CyclicAlias(name)) if name == "x"));
+}
+
+#[test]
+fn scalar_document() {
+    let doc = load("---\nfoo");
+    assert_eq!(doc.len(), 1);
+    assert_eq!(*doc[0], "foo");
+    assert!(doc.is_explicit());
+}
+
+#[test]
+fn multibyte_keys_and_aliases() {
+    let s = "名前: &name ランプ\nコピー: *name\nデバイス: {電源: オン}\n";
+    let mut doc = load(s);
+    assert_eq!(doc[0]["名前"], "ランプ");
+    assert_eq!(doc[0]["デバイス"]["電源"], "オン");
+    assert_eq!(doc.anchors()["name"].as_str(), Some("ランプ"));
+    doc.resolve_all().unwrap();
+    assert_eq!(doc[0]["コピー"], "ランプ");
+    let text = doc[0].to_string();
+    assert!(text.contains("名前: &name ランプ"));
+    assert_eq!(load(&text)[0]["デバイス"]["電源"], "オン");
+    // libyaml only takes ASCII letters, digits, `-` and `_` in anchor names.
+    assert!(matches!(
+        "a: &名 1\n".parse::<YamlDocument>(),
+        Err(YamlError::Syntax { .. })
+    ));
+}
+
+#[test]
+fn block_scalar_chomping_round_trips() {
+    for (indicator, value) in [
+        ("|-", "line\nnext"),
+        ("|+", "line\nnext\n\n"),
+        (">-", "line next"),
+        (">+", "line next\n\n"),
+    ] {
+        let doc = load(&format!("code: {}\n  line\n  next\n\n", indicator));
+        assert_eq!(doc[0]["code"], value, "{}", indicator);
+        let again = load(&doc[0].to_string());
+        assert_eq!(again[0]["code"], doc[0]["code"], "{}", indicator);
+    }
+}
+
+#[test]
+fn duplicate_key_rejected_in_strict_mode() {
+    let s = "device:\n  id: 1\n  id: 2\n";
+    let strict = YamlDocumentBuilder::new()
+        .duplicate_keys(DuplicateKeys::Reject)
+        .build_from_str(s);
+    assert!(matches!(strict, Err(YamlError::DuplicateKey(key, _)) if key == "id"));
+    assert_eq!(load(s)[0]["device"]["id"], 2);
+}
+
+#[test]
+fn keys_of_different_types_collide() {
+    assert!(matches!(
+        "1: a\n\"1\": b\n".parse::<YamlDocument>(),
+        Err(YamlError::KeyCollision(key, _)) if key == "1"
+    ));
+    let strict = YamlDocumentBuilder::new()
+        .duplicate_keys(DuplicateKeys::Reject)
+        .build_from_str("1: a\n!!int 1: b\n");
+    assert!(matches!(strict, Err(YamlError::DuplicateKey(key, _)) if key == "1"));
+    assert_eq!(load("id: 1\n\"id\": 2\n")[0]["id"], 2);
+    assert!("yes: 1\n'yes': 2\n".parse::<YamlDocument>().is_err());
+    let core = YamlDocumentBuilder::new()
+        .scalar_resolver(CoreSchema)
+        .build_from_str("yes: 1\n'yes': 2\n")
+        .unwrap();
+    assert_eq!(core[0]["yes"], 2);
+}
+
+#[test]
+fn anchored_key_resolves_as_alias_key() {
+    let doc = load("a:\n  &k name: value\nb:\n  *k: other\n");
+    assert_eq!(doc[0]["a"]["name"], "value");
+    assert_eq!(doc[0]["b"]["name"], "other");
+    assert_eq!(doc.anchors()["k"].as_str(), Some("name"));
+}
+
+#[test]
+fn complex_keys_are_rejected() {
+    for s in ["? [a, b]\n: v\n", "{a: 1}: v\n", "s: &s [1]\n*s : v\n"] {
+        assert!(
+            matches!(s.parse::<YamlDocument>(), Err(YamlError::ComplexKey(_))),
+            "{}",
+            s
+        );
+    }
+}
+
+#[test]
+fn byte_order_marks_select_the_encoding() {
+    let doc = YamlDocument::from_slice(b"\xEF\xBB\xBFname: lamp\n").unwrap();
+    assert_eq!(doc[0]["name"], "lamp");
+    assert!(!doc[0].contains_key("\u{feff}name"));
+    let mut utf16 = vec![0xFF, 0xFE];
+    utf16.extend("name: ランプ\n".encode_utf16().flat_map(u16::to_le_bytes));
+    let doc = YamlDocument::from_slice(&utf16).unwrap();
+    assert_eq!(doc[0]["name"], "ランプ");
+    let doc = YamlDocumentBuilder::new()
+        .encoding(Encoding::Utf16Le)
+        .build_from_slice(&utf16[2..])
+        .unwrap();
+    assert_eq!(doc[0]["name"], "ランプ");
+}
+
+#[test]
+fn tag_directives_expand_and_round_trip() {
+    let doc = load("%TAG !dev! tag:example.com,2024:\n---\nlamp: !dev!light on\n");
+    assert_eq!(doc[0]["lamp"].get_tag(), Some("tag:example.com,2024:light"));
+    let directives = doc.tag_directives();
+    assert_eq!(directives.len(), 1);
+    assert_eq!(directives[0].handle, "!dev!");
+    assert_eq!(directives[0].prefix, "tag:example.com,2024:");
+    let mut out = vec![];
+    doc.to_writer(&mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("%TAG !dev! tag:example.com,2024:"), "{}", out);
+    assert!(out.contains("!dev!light on"), "{}", out);
+}
+
+#[cfg(feature = "chrono")]
+#[test]
+fn timestamps_parse_in_yaml_forms() {
+    let doc = load(concat!(
+        "date: 2002-12-14\n",
+        "iso: 2001-12-14t21:59:43.10-05:00\n",
+        "spaced: 2001-12-14 21:59:43.10 -5\n",
+        "utc: 2001-12-15T02:59:43.1Z\n",
+        "month: 2002-13-01\n",
+        "word: yesterday\n",
+        "offset: 2001-12-14T21:59:43+5:3x\n",
+        "string: !!str 2002-12-14\n",
+    ));
+    let at = |key: &str| doc[0][key].as_datetime().map(|t| t.to_rfc3339());
+    assert_eq!(at("date").as_deref(), Some("2002-12-14T00:00:00+00:00"));
+    assert_eq!(at("iso").as_deref(), Some("2001-12-14T21:59:43.100-05:00"));
+    assert_eq!(at("spaced"), at("iso"));
+    assert_eq!(doc[0]["utc"].as_datetime(), doc[0]["iso"].as_datetime());
+    for key in ["month", "word", "offset", "string"] {
+        assert_eq!(at(key), None, "{}", key);
+    }
+}
+
+#[test]
+fn integers_in_every_radix() {
+    let doc = load(concat!(
+        "hex: 0x1F\n",
+        "octal: 0o17\n",
+        "legacy: 017\n",
+        "binary: -0b1010\n",
+        "grouped: +1_000_000\n",
+        "signs: +-5\n",
+        "big: 9223372036854775808\n",
+        "huge: 0x1_0000_0000_0000_0000_0000_0000_0000_0000\n",
+        "digits: 0b102\n",
+    ));
+    let int = |key: &str| doc[0][key].as_i64();
+    assert_eq!(int("hex"), Some(31));
+    assert_eq!(int("octal"), Some(15));
+    assert_eq!(int("legacy"), Some(15));
+    assert_eq!(int("binary"), Some(-10));
+    assert_eq!(int("grouped"), Some(1_000_000));
+    assert_eq!(int("signs"), None);
+    assert_eq!(int("big"), None);
+    assert_eq!(doc[0]["big"].as_u64(), Some(1 << 63));
+    assert_eq!(doc[0]["huge"].as_u64(), None);
+    assert_eq!(int("digits"), None);
+}
+
+#[test]
+fn core_schema_types_ints_and_floats() {
+    let kind = |s: &str| {
+        CoreSchema
+            .resolve(s)
+            .unwrap()
+            .rsplit(':')
+            .next()
+            .unwrap()
+            .to_string()
+    };
+    for int in ["0", "-12", "+7", "0x1F", "0o17", "012"] {
+        assert_eq!(kind(int), "int", "{}", int);
+    }
+    for float in [
+        "1.5", "-.5", "1.", "1e3", "+2.5E-3", ".inf", "-.Inf", "+.INF", ".nan", ".NaN",
+    ] {
+        assert_eq!(kind(float), "float", "{}", float);
+    }
+    for string in [
+        ".", "1e", "0x", "0b101", "1_000", "-.nan", "1.2.3", "e3", "yes",
+    ] {
+        assert_eq!(kind(string), "str", "{}", string);
+    }
+}
+
+#[test]
+fn diff_lists_changes_by_path() {
+    let old = load("name: lamp\nports: [1, 2]\nkind: light\nlevel: 5\n");
+    let new = load("name: lamp\nports: [1, 3, 4]\nlevel: !!str 5\ncolor: red\n");
+    let key = |k: &str| PathSegment::Key(k.into());
+    let at = |k: &str, i| vec![key(k), PathSegment::Index(i)];
+    assert_eq!(
+        old[0].diff(&new[0]),
+        vec![
+            Change::Modified {
+                path: at("ports", 1),
+                old: &old[0]["ports"][1],
+                new: &new[0]["ports"][1],
+            },
+            Change::Added {
+                path: at("ports", 2),
+                new: &new[0]["ports"][2],
+            },
+            Change::Removed {
+                path: vec![key("kind")],
+                old: &old[0]["kind"],
+            },
+            Change::Modified {
+                path: vec![key("level")],
+                old: &old[0]["level"],
+                new: &new[0]["level"],
+            },
+            Change::Added {
+                path: vec![key("color")],
+                new: &new[0]["color"],
+            },
+        ]
+    );
+    assert!(old[0]
+        .diff(&load("name: 'lamp'\nports: [1, 2]\nkind: light\nlevel: 5\n")[0])
+        .is_empty());
+}
+
+#[test]
+fn schema_reports_every_mismatch_by_path() {
+    let schema = Schema::map()
+        .required("name", Kind::Str)
+        .required("ports", Kind::seq(Kind::Int))
+        .optional("power", Kind::Int)
+        .optional("color", Kind::Str)
+        .required("bus", Kind::Map(Schema::map().required("id", Kind::Int)));
+    let error = |path: &str, expected, found: &str| ValidationError {
+        path: path.into(),
+        expected,
+        found: found.into(),
+    };
+    let doc = load("name: lamp\nports: [1, two]\npower: high\nbus: {}\nextra: 1\n");
+    assert_eq!(
+        schema.validate(&doc[0]),
+        Err(vec![
+            error("ports/1", "integer", "scalar `two`"),
+            error("power", "integer", "scalar `high`"),
+            error("bus/id", "integer", "nothing"),
+        ])
+    );
+    let errors = schema.validate(&load("[1]")[0]).unwrap_err();
+    assert_eq!(errors, vec![error("", "map", "sequence")]);
+    assert_eq!(errors[0].to_string(), "/: expected map, found sequence");
+    let ok = load("name: lamp\nports: [1, 2]\nbus: {id: 3}\n");
+    assert_eq!(schema.validate(&ok[0]), Ok(()));
+}
+
+#[test]
+fn typed_path_names_the_failing_segment() {
+    let doc = load("chip:\n  ports: [1, two]\n  name: lamp\n");
+    let chip = || TypedPath::new(&doc[0]).key("chip");
+    assert_eq!(chip().key("ports").index(0).as_i64().unwrap(), 1);
+    assert_eq!(chip().pointer("/ports/0").as_u64().unwrap(), 1);
+    assert_eq!(chip().key("name").as_str().unwrap(), "lamp");
+    assert!(matches!(
+        chip().key("ports").index(3).key("id").get(),
+        Err(YamlError::MissingPath(path)) if path == "chip/ports/3"
+    ));
+    assert!(matches!(
+        chip().pointer("ports/1").as_i64(),
+        Err(YamlError::AtPath(path, e))
+            if path == "chip/ports/1" && matches!(*e, YamlError::TypeMismatch { expected: "integer", .. })
+    ));
+}
+
+#[test]
+fn yaml_get_indexes_and_converts() {
+    let doc =
+        load("chip:\n  ports:\n    - id: 7\n      on: yes\n      name: lamp\n      level: 0.5\n");
+    let key = "name";
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => "id" : i64),
+        Some(7)
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => "id" : u8),
+        Some(7)
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => "on" : bool),
+        Some(true)
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => (key) : str),
+        Some("lamp")
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => "level" : f64),
+        Some(0.5)
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 1 => "id" : i64),
+        None
+    );
+    assert_eq!(
+        yaml_get!(doc[0], "chip" => "ports" => 0 => "name" : i64),
+        None
+    );
+}
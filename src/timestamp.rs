@@ -0,0 +1,55 @@
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+
+use crate::YamlElement;
+
+impl YamlElement {
+    /// Parses the YAML timestamp forms: a bare `2002-12-14`, taken as
+    /// midnight UTC, or a date and time separated by `T`, `t` or spaces,
+    /// with an optional fraction and a `Z`, `-5` or `+05:30` style offset.
+    /// A missing offset means UTC.
+    pub fn as_datetime(&self) -> Option<DateTime<FixedOffset>> {
+        let s = self.typed_str(&["timestamp"])?.trim();
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Some(date.and_hms_opt(0, 0, 0)?.and_utc().fixed_offset());
+        }
+        let (date, time) = s.split_once(['T', 't', ' ', '\t'])?;
+        let time = time.trim_start();
+        let (time, offset) = match time.strip_suffix('Z') {
+            Some(time) => (time, 0),
+            None => match time.rfind(['+', '-']) {
+                Some(i) => (&time[..i], offset_seconds(&time[i..])?),
+                None => (time, 0),
+            },
+        };
+        let naive = NaiveDateTime::parse_from_str(
+            &format!("{} {}", date, time.trim_end()),
+            "%Y-%m-%d %H:%M:%S%.f",
+        )
+        .ok()?;
+        FixedOffset::east_opt(offset)?
+            .from_local_datetime(&naive)
+            .single()
+    }
+}
+
+/// Reads `+5`, `-05`, `+0530` or `-05:30`.
+fn offset_seconds(offset: &str) -> Option<i32> {
+    let (sign, digits) = offset.split_at(1);
+    if !digits.bytes().all(|b| b.is_ascii_digit() || b == b':') {
+        return None;
+    }
+    let (hours, minutes) = match digits.split_once(':') {
+        Some(parts) => parts,
+        None if digits.len() == 4 => digits.split_at(2),
+        None => (digits, "0"),
+    };
+    if !(hours.len() <= 2 && minutes.len() <= 2) {
+        return None;
+    }
+    let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+    Some(if sign == "-" { -seconds } else { seconds })
+}